@@ -0,0 +1,261 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    consist::CarKind,
+    track::{Event, Network, NetworkError, StationID, Train, TrainId},
+};
+
+/// How many crates fit in a wagon
+const CRATES_PER_WAGON: usize = 4;
+/// How many crates a station makes every second, unless it's set otherwise
+const DEFAULT_PRODUCTION: f32 = 0.05;
+/// How much shorter or longer than average the wait for a crate can be, as a
+/// fraction of the average
+const PRODUCTION_JITTER: f32 = 0.5;
+/// The most crates a station makes in one update, however fast it produces.
+/// Anything it would have made past that is lost.
+const MAX_CRATES_PER_UPDATE: usize = 16;
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Crate {
+    pub origin: StationID,
+    pub destination: StationID,
+    /// The time the crate was made at, in seconds since the network was
+    /// created
+    pub created: f32,
+}
+
+/// What a station makes and what's waiting there
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Production {
+    /// Crates per second, on average
+    pub(crate) rate: f32,
+    /// The part of the next crate made so far, which can start out below zero
+    /// when the wait for it is longer than average
+    pub(crate) progress: f32,
+    pub(crate) waiting: Vec<Crate>,
+}
+
+impl Default for Production {
+    fn default() -> Self {
+        Production {
+            rate: DEFAULT_PRODUCTION,
+            progress: 0.0,
+            waiting: vec![],
+        }
+    }
+}
+
+impl Train {
+    pub(crate) fn capacity(&self) -> usize {
+        self.cars
+            .iter()
+            .filter(|car| car.kind == CarKind::Wagon)
+            .count()
+            * CRATES_PER_WAGON
+    }
+}
+
+impl Network {
+    /// Sets how many crates the station makes every second on average, each
+    /// after a wait and for another station picked at random
+    pub fn set_production(&mut self, station: StationID, rate: f32) -> Result<(), NetworkError> {
+        if !(rate.is_finite() && rate >= 0.0) {
+            return Err(NetworkError::InvalidProduction(rate));
+        }
+
+        self.stations
+            .get_mut(station.0)
+            .ok_or(NetworkError::UnknownStation(station))?
+            .production
+            .rate = rate;
+
+        Ok(())
+    }
+
+    pub(crate) fn produce_crates(&mut self, delta_time: f32) {
+        // Crates need somewhere else to go
+        if self.stations.len() < 2 {
+            return;
+        }
+
+        let stations = self.stations.len();
+        for index in 0..stations {
+            let production = &mut self.stations[index].production;
+            production.progress += production.rate * delta_time;
+
+            let mut made = 0;
+            while production.progress >= 1.0 && made < MAX_CRATES_PER_UPDATE {
+                made += 1;
+                production.progress -= self
+                    .rng
+                    .gen_range(1.0 - PRODUCTION_JITTER..1.0 + PRODUCTION_JITTER);
+
+                let destination = (index + self.rng.gen_range(1..stations)) % stations;
+                production.waiting.push(Crate {
+                    origin: StationID(index),
+                    destination: StationID(destination),
+                    created: self.time,
+                });
+            }
+            production.progress = production.progress.min(1.0);
+        }
+    }
+
+    /// Unloads the crates the train carries for the station, then loads as
+    /// many of the ones waiting there as fit, oldest first
+    pub(crate) fn exchange_cargo(&mut self, train_id: TrainId, station: StationID) {
+        self.unload_cargo(train_id, station);
+        self.load_cargo(train_id, station);
+    }
+
+    pub(crate) fn unload_cargo(&mut self, train_id: TrainId, station: StationID) {
+        let train = &mut self.trains[train_id.0];
+        let (delivered, kept): (Vec<_>, Vec<_>) = train
+            .cargo
+            .drain(..)
+            .partition(|cargo| cargo.destination == station);
+        train.cargo = kept;
+
+        for cargo in delivered {
+            let revenue = self.delivery_revenue(&cargo);
+            self.balance += revenue;
+            self.events.push(Event::CrateDelivered {
+                train: train_id,
+                cargo,
+                revenue,
+            });
+        }
+    }
+
+    /// Loads as many of the crates waiting at the station as fit, oldest
+    /// first. Trains with orders only take crates for stations they go to.
+    pub(crate) fn load_cargo(&mut self, train_id: TrainId, station: StationID) {
+        let train = &mut self.trains[train_id.0];
+        let mut space = train.capacity() - train.cargo.len();
+
+        self.stations[station.0].production.waiting.retain(|cargo| {
+            let wanted = train.orders.is_empty() || train.serves(cargo.destination);
+            if space == 0 || !wanted {
+                return true;
+            }
+
+            train.cargo.push(*cargo);
+            space -= 1;
+            false
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{orders::Order, track::generate_network};
+
+    fn crate_for(destination: usize, created: f32) -> Crate {
+        Crate {
+            origin: StationID(1 - destination),
+            destination: StationID(destination),
+            created,
+        }
+    }
+
+    #[test]
+    fn production_rates_must_be_finite_and_not_negative() {
+        let mut network = generate_network(0);
+
+        for rate in [f32::INFINITY, f32::NAN, -0.1] {
+            assert!(matches!(
+                network.set_production(StationID(0), rate),
+                Err(NetworkError::InvalidProduction(_))
+            ));
+        }
+        assert_eq!(network.set_production(StationID(0), 0.0), Ok(()));
+        assert_eq!(
+            network.set_production(StationID(9), 1.0),
+            Err(NetworkError::UnknownStation(StationID(9)))
+        );
+    }
+
+    #[test]
+    fn stations_make_crates_for_other_stations() {
+        let mut network = generate_network(0);
+        network.set_production(StationID(0), 1.0).unwrap();
+        network.set_production(StationID(1), 0.0).unwrap();
+
+        for _ in 0..100 {
+            network.produce_crates(0.1);
+        }
+
+        let made = &network.stations[0].production.waiting;
+        assert!((6..=20).contains(&made.len()), "{} crates", made.len());
+        assert!(made.iter().all(|cargo| cargo.destination == StationID(1)));
+        assert!(network.stations[1].production.waiting.is_empty());
+    }
+
+    #[test]
+    fn one_update_makes_a_limited_number_of_crates() {
+        let mut network = generate_network(0);
+        network.set_production(StationID(0), f32::MAX).unwrap();
+
+        network.produce_crates(1.0);
+        network.produce_crates(1.0);
+
+        let made = network.stations[0].production.waiting.len();
+        assert_eq!(made, 2 * MAX_CRATES_PER_UPDATE);
+    }
+
+    #[test]
+    fn trains_load_the_oldest_crates_that_fit() {
+        let mut network = generate_network(0);
+        let train = network.train_ids().next().unwrap();
+        let capacity = network.trains[train.0].capacity();
+        network.stations[0].production.waiting =
+            (0..capacity + 2).map(|i| crate_for(1, i as f32)).collect();
+
+        network.load_cargo(train, StationID(0));
+
+        let cargo = &network.trains[train.0].cargo;
+        assert_eq!(cargo.len(), capacity);
+        assert!(cargo
+            .iter()
+            .zip(0..)
+            .all(|(cargo, i)| cargo.created == i as f32));
+        assert_eq!(network.stations[0].production.waiting.len(), 2);
+    }
+
+    #[test]
+    fn trains_with_orders_only_load_crates_for_where_they_go() {
+        let mut network = generate_network(0);
+        let train = network.train_ids().next().unwrap();
+        network
+            .set_orders(train, vec![Order::GoTo(StationID(0))])
+            .unwrap();
+        network.stations[0].production.waiting = vec![crate_for(1, 0.0)];
+
+        network.load_cargo(train, StationID(0));
+
+        assert!(network.trains[train.0].cargo.is_empty());
+    }
+
+    #[test]
+    fn crates_are_delivered_and_paid_for_at_their_destination() {
+        let mut network = generate_network(0);
+        let train = network.train_ids().next().unwrap();
+        network.trains[train.0].cargo = vec![crate_for(1, 0.0), crate_for(0, 0.0)];
+        network.drain_events().for_each(drop);
+        let balance = network.balance;
+
+        network.unload_cargo(train, StationID(1));
+
+        assert_eq!(network.trains[train.0].cargo, vec![crate_for(0, 0.0)]);
+        let events: Vec<_> = network.drain_events().collect();
+        let [Event::CrateDelivered { cargo, revenue, .. }] = events[..] else {
+            panic!("{events:?}");
+        };
+        assert_eq!(cargo, crate_for(1, 0.0));
+        assert!(revenue > 0.0);
+        assert_eq!(network.balance, balance + revenue);
+    }
+}
@@ -0,0 +1,271 @@
+use glam::Vec2;
+
+use crate::{
+    consist::{body_transform, BOGIE_INSET, LOCOMOTIVE_LENGTH},
+    track::{Event, Network, Track, TrackID, TRAIN_WIDTH},
+};
+
+const CONFLICT_SAMPLE_SPACING: f32 = 0.5;
+
+/// Whether two rectangles overlap, given their centers, rotations and sizes.
+/// Two rectangles are apart exactly when one of their sides' directions
+/// separates them.
+fn rectangles_overlap(a: (Vec2, f32), a_size: Vec2, b: (Vec2, f32), b_size: Vec2) -> bool {
+    let forwards = [Vec2::from_angle(a.1), Vec2::from_angle(b.1)];
+    let offset = b.0 - a.0;
+
+    forwards
+        .iter()
+        .flat_map(|forward| [*forward, forward.perp()])
+        .all(|axis| {
+            let extent = |forward: Vec2, size: Vec2| {
+                (size.x * forward.dot(axis).abs() + size.y * forward.perp().dot(axis).abs()) * 0.5
+            };
+
+            offset.dot(axis).abs() < extent(forwards[0], a_size) + extent(forwards[1], b_size)
+        })
+}
+
+/// Where a locomotive would be along the track, every
+/// `CONFLICT_SAMPLE_SPACING`, with both its bogies kept on the track
+fn track_samples(track: &Track) -> Vec<(Vec2, f32)> {
+    let steps = (track.length / CONFLICT_SAMPLE_SPACING).ceil().max(1.0) as usize;
+    let half_spacing = LOCOMOTIVE_LENGTH * 0.5 - BOGIE_INSET;
+    let bogie = |distance: f32| {
+        track
+            .shape
+            .get_transform_at_distance(distance.clamp(0.0, track.length))
+            .0
+    };
+
+    (0..=steps)
+        .map(|step| {
+            let distance = track.length * step as f32 / steps as f32;
+            body_transform(
+                bogie(distance + half_spacing),
+                bogie(distance - half_spacing),
+            )
+        })
+        .collect()
+}
+
+impl Network {
+    /// Tracks less than `distance` ahead of or behind the track, not counting
+    /// their own length
+    fn tracks_along(&self, track_id: TrackID, distance: f32) -> Vec<TrackID> {
+        let track = &self.tracks[track_id.0];
+        let mut found = vec![];
+        let mut ahead = vec![(track.destiation, 0.0)];
+        let mut behind = vec![(track.source, 0.0)];
+
+        while let Some((junction, gap)) = ahead.pop() {
+            for exit in &self.junctions[junction.0].exits {
+                found.push(*exit);
+
+                let exit = &self.tracks[exit.0];
+                if gap + exit.length < distance {
+                    ahead.push((exit.destiation, gap + exit.length));
+                }
+            }
+        }
+        while let Some((junction, gap)) = behind.pop() {
+            for enterance in &self.junctions[junction.0].enterances {
+                found.push(*enterance);
+
+                let enterance = &self.tracks[enterance.0];
+                if gap + enterance.length < distance {
+                    behind.push((enterance.source, gap + enterance.length));
+                }
+            }
+        }
+
+        found
+    }
+
+    /// Tracks close enough to the track that locomotives on both could touch,
+    /// such as ones crossing it or merging with it. Tracks just ahead or behind
+    /// are left out, trains only touch on those by running into each other
+    /// along the line.
+    pub(crate) fn find_conflicts(&self, track_id: TrackID) -> Vec<TrackID> {
+        let size = Vec2::new(LOCOMOTIVE_LENGTH, TRAIN_WIDTH);
+        let track = &self.tracks[track_id.0];
+        let middle = track.shape.get_transform_at_distance(track.length * 0.5).0;
+        let samples = track_samples(track);
+        let along = self.tracks_along(track_id, LOCOMOTIVE_LENGTH);
+
+        self.tracks
+            .iter()
+            .filter(|other| other.id != track.id && !along.contains(&other.id))
+            .filter(|other| {
+                let other_middle = other.shape.get_transform_at_distance(other.length * 0.5).0;
+
+                middle.distance(other_middle) <= (track.length + other.length) * 0.5 + size.length()
+            })
+            .filter(|other| {
+                let other_samples = track_samples(other);
+
+                samples.iter().any(|sample| {
+                    other_samples
+                        .iter()
+                        .any(|other_sample| rectangles_overlap(*sample, size, *other_sample, size))
+                })
+            })
+            .map(|other| other.id)
+            .collect()
+    }
+
+    /// Stops every train with a car overlapping a car of another train,
+    /// recording a crash for each pair that wasn't already crashed into each
+    /// other
+    pub(crate) fn detect_collisions(&mut self) {
+        let cars: Vec<Vec<_>> = self
+            .trains
+            .iter()
+            .map(|train| {
+                // Trains in depots can't run into anything
+                if !train.on_network() {
+                    return vec![];
+                }
+
+                self.car_transforms(train)
+                    .into_iter()
+                    .zip(&train.cars)
+                    .map(|(transform, car)| (transform, Vec2::new(car.length, TRAIN_WIDTH)))
+                    .collect()
+            })
+            .collect();
+
+        for a in 0..self.trains.len() {
+            for b in a + 1..self.trains.len() {
+                if self.trains[a].crashed && self.trains[b].crashed {
+                    continue;
+                }
+
+                let overlap = cars[a]
+                    .iter()
+                    .flat_map(|a| cars[b].iter().map(move |b| (a, b)))
+                    .find(|((a, a_size), (b, b_size))| {
+                        // Too far apart for even the corners to touch
+                        a.0.distance(b.0) <= (a_size.length() + b_size.length()) * 0.5
+                            && rectangles_overlap(*a, *a_size, *b, *b_size)
+                    });
+                let Some(((a_car, _), (b_car, _))) = overlap else {
+                    continue;
+                };
+
+                self.trains[a].crashed = true;
+                self.trains[b].crashed = true;
+                self.events.push(Event::Crash {
+                    trains: [self.trains[a].id, self.trains[b].id],
+                    position: (a_car.0 + b_car.0) * 0.5,
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_4;
+
+    use super::*;
+
+    /// A network with a straight track between each pair of points
+    fn network_with_tracks(lines: &[(Vec2, Vec2)]) -> Network {
+        let mut network = Network::new(0);
+        network.balance = 1e6;
+
+        for (source, destination) in lines {
+            let source = network.add_junction(*source);
+            let destination = network.add_junction(*destination);
+            network.try_connect_track(source, destination).unwrap();
+        }
+
+        network
+    }
+
+    #[test]
+    fn rotated_rectangles_overlap_only_when_no_side_separates_them() {
+        let bar = Vec2::new(4.0, 0.5);
+
+        // Crossing at right angles
+        assert!(rectangles_overlap(
+            (Vec2::ZERO, 0.0),
+            bar,
+            (Vec2::ZERO, FRAC_PI_4 * 2.0),
+            bar
+        ));
+        // A diagonal bar with its end over the middle of a flat one
+        assert!(rectangles_overlap(
+            (Vec2::ZERO, 0.0),
+            bar,
+            (Vec2::new(1.2, 1.2), FRAC_PI_4),
+            bar
+        ));
+        // Side by side diagonals, close enough that their bounding boxes
+        // overlap but apart across their width
+        let beside = Vec2::from_angle(FRAC_PI_4).perp() * 1.0;
+        assert!(!rectangles_overlap(
+            (Vec2::ZERO, FRAC_PI_4),
+            bar,
+            (beside, FRAC_PI_4),
+            bar
+        ));
+        // End to end along the same line
+        assert!(!rectangles_overlap(
+            (Vec2::ZERO, FRAC_PI_4),
+            bar,
+            (Vec2::from_angle(FRAC_PI_4) * 4.5, FRAC_PI_4),
+            bar
+        ));
+    }
+
+    #[test]
+    fn crossing_tracks_conflict() {
+        let network = network_with_tracks(&[
+            (Vec2::new(-10.0, 0.0), Vec2::new(10.0, 0.0)),
+            (Vec2::new(0.0, -10.0), Vec2::new(0.0, 10.0)),
+            (Vec2::new(-10.0, 20.0), Vec2::new(10.0, 20.0)),
+        ]);
+        let ends = |track: &TrackID| {
+            let track = &network.tracks[track.0];
+            [track.source, track.destiation].map(|id| network.junctions[id.0].position)
+        };
+        // Tracks running across at the height
+        let across = |y: f32| {
+            (0..network.tracks.len())
+                .map(TrackID)
+                .filter(move |track| ends(track).iter().all(|end| end.y == y))
+        };
+
+        let crossing: Vec<_> = across(0.0)
+            .flat_map(|track| &network.tracks[track.0].conflicts)
+            .collect();
+        assert!(!crossing.is_empty());
+        assert!(crossing
+            .iter()
+            .all(|track| ends(track).iter().all(|end| end.x.abs() < 1e-3)));
+        assert!(across(20.0).all(|track| network.tracks[track.0].conflicts.is_empty()));
+    }
+
+    #[test]
+    fn overlapping_trains_crash_once() {
+        let mut network = network_with_tracks(&[(Vec2::ZERO, Vec2::new(10.0, 0.0))]);
+        let track = network.tracks[network.tracks.len() - 1].id;
+        let first = network.try_add_train(track).unwrap();
+        let second = network.try_add_train(track).unwrap();
+
+        network.detect_collisions();
+        network.detect_collisions();
+
+        let crashes: Vec<_> = network
+            .drain_events()
+            .filter_map(|event| match event {
+                Event::Crash { trains, .. } => Some(trains),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(crashes, vec![[first, second]]);
+        assert!(network.trains[first.0].crashed && network.trains[second.0].crashed);
+    }
+}
@@ -0,0 +1,294 @@
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+
+use crate::track::{Network, NetworkError, TrackID, Train, TrainId};
+
+pub const LOCOMOTIVE_LENGTH: f32 = 1.7;
+/// Space between the ends of two coupled cars
+const COUPLING_GAP: f32 = 0.2;
+/// How far in from each end of a car its bogies are
+pub(crate) const BOGIE_INSET: f32 = 0.3;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CarKind {
+    Locomotive,
+    Wagon,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Car {
+    pub(crate) kind: CarKind,
+    pub(crate) length: f32,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CarInfo {
+    pub train: TrainId,
+    pub kind: CarKind,
+    pub position: Vec2,
+    pub angle: f32,
+    pub length: f32,
+    pub crashed: bool,
+}
+
+/// Whether a car can be `length` long, which has to be finite and positive
+pub(crate) fn valid_car_length(length: f32) -> bool {
+    length.is_finite() && length > 0.0
+}
+
+/// A locomotive pulling wagons of the given lengths
+pub(crate) fn consist(wagons: &[f32]) -> Result<Vec<Car>, NetworkError> {
+    if let Some(length) = wagons.iter().find(|length| !valid_car_length(**length)) {
+        return Err(NetworkError::InvalidWagonLength(*length));
+    }
+
+    Ok(std::iter::once(Car {
+        kind: CarKind::Locomotive,
+        length: LOCOMOTIVE_LENGTH,
+    })
+    .chain(wagons.iter().map(|length| Car {
+        kind: CarKind::Wagon,
+        length: *length,
+    }))
+    .collect())
+}
+
+/// Where the middle of a car resting on bogies at the two points is, and
+/// which way it faces. On curves the body cuts the corner between them.
+pub(crate) fn body_transform(front_bogie: Vec2, rear_bogie: Vec2) -> (Vec2, f32) {
+    (
+        (front_bogie + rear_bogie) * 0.5,
+        (front_bogie - rear_bogie).to_angle(),
+    )
+}
+
+impl Train {
+    /// From the front of the first car to the back of the last
+    pub(crate) fn length(&self) -> f32 {
+        self.cars.iter().map(|car| car.length).sum::<f32>()
+            + COUPLING_GAP * (self.cars.len() - 1) as f32
+    }
+}
+
+impl Network {
+    /// The track and distance along it `offset` behind the front of the train,
+    /// going back along the tracks the train came from
+    pub(crate) fn position_behind(&self, train: &Train, offset: f32) -> (TrackID, f32) {
+        let (index, distance) = self.index_behind(train, offset);
+        let track = match index {
+            0 => train.track,
+            index => train.trailing[index - 1],
+        };
+
+        (track, distance)
+    }
+
+    /// Like `position_behind`, but with how many tracks back from the front
+    /// track the position is
+    fn index_behind(&self, train: &Train, offset: f32) -> (usize, f32) {
+        let front = &self.tracks[train.track.0];
+        // Where the train left each track, and how much of it is behind that
+        let (mut index, mut from) = (0, train.distance);
+        let mut behind = if train.backwards {
+            front.length - train.distance
+        } else {
+            train.distance
+        };
+        let mut left = offset;
+
+        for next in &train.trailing {
+            if left <= behind {
+                break;
+            }
+
+            left -= behind;
+            let length = self.tracks[next.0].length;
+            (index, from, behind) = (
+                index + 1,
+                if train.backwards { 0.0 } else { length },
+                length,
+            );
+        }
+
+        let left = left.min(behind);
+        let distance = if train.backwards {
+            from + left
+        } else {
+            from - left
+        };
+
+        (index, distance)
+    }
+
+    /// Where the middle of every car of the train is, first car first, placed
+    /// on its bogies
+    pub(crate) fn car_transforms(&self, train: &Train) -> Vec<(Vec2, f32)> {
+        let bogie = |offset: f32| {
+            let (track, distance) = self.position_behind(train, offset);
+            self.tracks[track.0]
+                .shape
+                .get_transform_at_distance(distance)
+                .0
+        };
+        let mut front = 0.0;
+
+        train
+            .cars
+            .iter()
+            .map(|car| {
+                let transform = body_transform(
+                    bogie(front + BOGIE_INSET),
+                    bogie(front + car.length - BOGIE_INSET),
+                );
+                front += car.length + COUPLING_GAP;
+
+                transform
+            })
+            .collect()
+    }
+
+    /// Frees the tracks the back of the train has left
+    pub(crate) fn trim_trailing(&mut self, train_id: TrainId) {
+        let train = &self.trains[train_id.0];
+        let length = train.length();
+        let front = &self.tracks[train.track.0];
+        let mut covered = if train.backwards {
+            front.length - train.distance
+        } else {
+            train.distance
+        };
+
+        let kept = train
+            .trailing
+            .iter()
+            .take_while(|track| {
+                let needed = covered < length;
+                covered += self.tracks[track.0].length;
+                needed
+            })
+            .count();
+
+        for track in self.trains[train_id.0].trailing.drain(kept..) {
+            let track = &mut self.tracks[track.0];
+            track.trains.retain(|id| *id != train_id);
+            if track.reserved_by == Some(train_id) {
+                track.reserved_by = None;
+            }
+        }
+    }
+
+    /// Moves the front of the train to the back of its last car, so it runs
+    /// the other way along the same tracks
+    pub(crate) fn turn_around(&mut self, train_id: TrainId) {
+        let train = &self.trains[train_id.0];
+        let (index, distance) = self.index_behind(train, train.length());
+
+        let train = &mut self.trains[train_id.0];
+        let mut covered: Vec<_> = std::iter::once(train.track)
+            .chain(train.trailing.drain(..))
+            .collect();
+        // Tracks left over past the back of the train, which can happen when
+        // it ends right at a junction
+        let past = covered.split_off(index + 1);
+        let back = covered.pop().unwrap();
+
+        train.track = back;
+        train.distance = distance;
+        train.trailing = covered.iter().copied().rev().collect();
+        train.cars.reverse();
+        train.backwards = !train.backwards;
+
+        for track in past {
+            if track == back || covered.contains(&track) {
+                continue;
+            }
+
+            let track = &mut self.tracks[track.0];
+            track.trains.retain(|id| *id != train_id);
+            if track.reserved_by == Some(train_id) {
+                track.reserved_by = None;
+            }
+        }
+    }
+
+    pub fn cars(&self) -> impl Iterator<Item = CarInfo> + '_ {
+        self.trains
+            .iter()
+            .filter(|train| train.on_network())
+            .flat_map(|train| {
+                self.car_transforms(train).into_iter().zip(&train.cars).map(
+                    |((position, angle), car)| CarInfo {
+                        train: train.id,
+                        kind: car.kind,
+                        position,
+                        angle,
+                        length: car.length,
+                        crashed: train.crashed,
+                    },
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec2;
+
+    use crate::{
+        depot::DepotID,
+        track::{generate_network, Network, NetworkError},
+    };
+
+    #[test]
+    fn turning_around_at_a_junction_frees_the_track_behind() {
+        let mut network = Network::new(0);
+        let junctions: Vec<_> = (0..3)
+            .map(|i| network.add_junction(Vec2::new(i as f32 * 10.0, 0.0)))
+            .collect();
+        let first = network
+            .try_connect_track(junctions[0], junctions[1])
+            .unwrap();
+        let second = network
+            .try_connect_track(junctions[1], junctions[2])
+            .unwrap();
+        let train_id = network.try_add_train(first).unwrap();
+
+        // Just after the front moved onto the second track, with the back of
+        // the train right at the junction between them
+        let train = &mut network.trains[train_id.0];
+        train.track = second;
+        train.distance = train.length();
+        train.trailing.push_back(first);
+        network.tracks[second.0].trains.push_back(train_id);
+
+        network.turn_around(train_id);
+
+        let train = &network.trains[train_id.0];
+        assert_eq!((train.track, train.distance), (second, 0.0));
+        assert!(train.trailing.is_empty());
+        assert!(network.tracks[first.0].trains.is_empty());
+        assert_eq!(network.invariant_violations(), vec![]);
+    }
+
+    #[test]
+    fn wagons_must_have_a_length() {
+        let mut network = generate_network(0);
+        let track = network.trains[0].track;
+        let balance = network.balance;
+
+        for length in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+            assert!(matches!(
+                network.try_add_consist(track, &[1.5, length]),
+                Err(NetworkError::InvalidWagonLength(_))
+            ));
+            assert!(matches!(
+                network.buy_train(DepotID(0), &[length]),
+                Err(NetworkError::InvalidWagonLength(_))
+            ));
+        }
+        assert_eq!(network.balance, balance);
+
+        network.buy_train(DepotID(0), &[1.5]).unwrap();
+        assert!(Network::load(&network.save()).is_ok());
+    }
+}
@@ -0,0 +1,343 @@
+use std::collections::VecDeque;
+
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    consist::consist,
+    economy::{consist_price, SERVICE_COST},
+    track::{Destination, Event, JunctionId, Network, NetworkError, Train, TrainId},
+};
+
+/// How long servicing a train takes, in seconds
+const SERVICE_TIME: f32 = 20.0;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DepotID(pub(crate) usize);
+
+/// What a train sent to a depot does there
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DepotVisit {
+    /// Stay in the depot until it's dispatched again
+    Store,
+    /// Get serviced, then leave again by itself
+    Service,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum Location {
+    Network,
+    Depot(DepotID),
+    /// Gone for good, its id isn't used again
+    Removed,
+}
+
+/// Where trains are kept off the network. Trains leave onto the tracks the
+/// junction's switches are set to and come back by stopping at the junction.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Depot {
+    pub(crate) junction: JunctionId,
+    /// Trains dispatched but waiting for the tracks to be clear, first to
+    /// leave first
+    pub(crate) departures: VecDeque<TrainId>,
+    /// Trains being serviced and how much longer it takes
+    pub(crate) servicing: Vec<(TrainId, f32)>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DepotInfo {
+    pub position: Vec2,
+    /// How many trains are in the depot
+    pub trains: usize,
+}
+
+impl Train {
+    pub(crate) fn on_network(&self) -> bool {
+        self.location == Location::Network
+    }
+}
+
+impl Network {
+    fn depot(&self, id: DepotID) -> Result<&Depot, NetworkError> {
+        self.depots.get(id.0).ok_or(NetworkError::UnknownDepot(id))
+    }
+
+    pub fn add_depot(&mut self, junction: JunctionId) -> Result<DepotID, NetworkError> {
+        self.junction(junction)?;
+
+        self.depots.push(Depot {
+            junction,
+            departures: VecDeque::new(),
+            servicing: vec![],
+        });
+
+        Ok(DepotID(self.depots.len() - 1))
+    }
+
+    /// Buys a locomotive pulling wagons of the given lengths, which waits in
+    /// the depot until it's dispatched
+    pub fn buy_train(&mut self, depot: DepotID, wagons: &[f32]) -> Result<TrainId, NetworkError> {
+        self.depot(depot)?;
+        let cars = consist(wagons)?;
+        self.charge(consist_price(&cars))?;
+
+        let train_id = TrainId(self.trains.len());
+        let mut train = Train::new(train_id, cars);
+        train.location = Location::Depot(depot);
+        self.trains.push(train);
+
+        Ok(train_id)
+    }
+
+    /// Sends the train out of its depot as soon as the tracks it leaves onto
+    /// are clear
+    pub fn dispatch_train(&mut self, train_id: TrainId) -> Result<(), NetworkError> {
+        let Location::Depot(depot) = self.train(train_id)?.location else {
+            return Err(NetworkError::TrainNotInDepot(train_id));
+        };
+        let depot = &mut self.depots[depot.0];
+
+        // Trains being serviced leave once they're done anyway
+        if !depot.departures.contains(&train_id)
+            && !depot.servicing.iter().any(|(train, _)| *train == train_id)
+        {
+            depot.departures.push_back(train_id);
+        }
+
+        Ok(())
+    }
+
+    /// Sends the train to the depot, where it stops at the junction and goes
+    /// in. Trains keep their orders and go back to them when dispatched again.
+    pub fn send_to_depot(
+        &mut self,
+        train_id: TrainId,
+        depot_id: DepotID,
+        visit: DepotVisit,
+    ) -> Result<(), NetworkError> {
+        let junction = self.depot(depot_id)?.junction;
+        self.set_destination(train_id, Destination::Junction(junction))?;
+        self.trains[train_id.0].bound_for = Some((depot_id, visit));
+
+        Ok(())
+    }
+
+    /// Takes the train away for good, wherever it is. Wrecks can be cleared
+    /// off the network this way.
+    pub fn remove_train(&mut self, train_id: TrainId) -> Result<(), NetworkError> {
+        if let Location::Depot(depot) = self.train(train_id)?.location {
+            let depot = &mut self.depots[depot.0];
+            depot.departures.retain(|train| *train != train_id);
+            depot.servicing.retain(|(train, _)| *train != train_id);
+        }
+
+        self.take_off_network(train_id, Location::Removed);
+
+        Ok(())
+    }
+
+    /// Frees everything the train is on or has reserved and moves it to the
+    /// location, which must be off the network
+    fn take_off_network(&mut self, train_id: TrainId, location: Location) {
+        for track in &mut self.tracks {
+            track.trains.retain(|train| *train != train_id);
+            if track.reserved_by == Some(train_id) {
+                track.reserved_by = None;
+            }
+        }
+
+        let train = &mut self.trains[train_id.0];
+        train.location = location;
+        train.trailing.clear();
+        train.path.clear();
+        train.route.clear();
+        train.destination = None;
+        train.bound_for = None;
+        train.dwelling = None;
+        train.departure_due = None;
+        train.speed = 0.0;
+        train.reversing = false;
+    }
+
+    /// Takes the train, which has stopped at the junction of the depot, into
+    /// the depot. Trains sent for a service that can't be paid for are stored
+    /// instead.
+    pub(crate) fn enter_depot(&mut self, train_id: TrainId, depot: DepotID) {
+        let visit = self.trains[train_id.0]
+            .bound_for
+            .map_or(DepotVisit::Store, |(_, visit)| visit);
+
+        self.take_off_network(train_id, Location::Depot(depot));
+        if visit == DepotVisit::Service && self.charge(SERVICE_COST).is_ok() {
+            self.depots[depot.0]
+                .servicing
+                .push((train_id, SERVICE_TIME));
+        }
+
+        self.events.push(Event::EnteredDepot {
+            train: train_id,
+            depot,
+        });
+    }
+
+    /// Finishes servicing trains and lets the next train waiting to leave each
+    /// depot out if it can
+    pub(crate) fn update_depots(&mut self, delta_time: f32) {
+        for index in 0..self.depots.len() {
+            let depot = &mut self.depots[index];
+
+            let mut serviced = vec![];
+            depot.servicing.retain_mut(|(train, left)| {
+                *left -= delta_time;
+                if *left > 0.0 {
+                    return true;
+                }
+
+                serviced.push(*train);
+                false
+            });
+            for train in serviced {
+                self.trains[train.0].wear = 0.0;
+                depot.departures.push_back(train);
+            }
+
+            if let Some(&train) = depot.departures.front() {
+                if self.try_leave_depot(DepotID(index), train) {
+                    self.depots[index].departures.pop_front();
+                }
+            }
+        }
+    }
+
+    /// Puts the train on the tracks the depot's junction leads to, with its
+    /// back at the junction, if they're clear. Trains right by the junction
+    /// on any of its tracks would be in the way too.
+    fn try_leave_depot(&mut self, depot: DepotID, train_id: TrainId) -> bool {
+        let mut junction = &self.junctions[self.depots[depot.0].junction.0];
+        // Depots at the end of a line let trains out the other way
+        let backwards = junction.exits.is_empty();
+        let length = self.trains[train_id.0].length();
+
+        if (&junction.enterances)
+            .into_iter()
+            .chain(&junction.exits)
+            .any(|track| self.track_taken(*track, None))
+        {
+            return false;
+        }
+
+        let mut covered = vec![];
+        let mut behind = 0.0;
+        loop {
+            let leaving = junction.leaving(backwards);
+            if leaving.is_empty() {
+                return false;
+            }
+
+            let track = &self.tracks[leaving[junction.switch_for(backwards)].0];
+            if std::iter::once(&track.id)
+                .chain(&track.conflicts)
+                .any(|track| self.track_taken(*track, None))
+            {
+                return false;
+            }
+
+            covered.push(track.id);
+            if behind + track.length >= length {
+                break;
+            }
+
+            behind += track.length;
+            junction = &self.junctions[track.end(backwards).0];
+        }
+
+        let front = covered.pop().unwrap();
+        let into = length - behind;
+
+        let train = &mut self.trains[train_id.0];
+        train.location = Location::Network;
+        train.track = front;
+        train.distance = if backwards {
+            self.tracks[front.0].length - into
+        } else {
+            into
+        };
+        train.backwards = backwards;
+        train.trailing = covered.into_iter().rev().collect();
+        train.needs_path = true;
+
+        for track in std::iter::once(front).chain(train.trailing.iter().copied()) {
+            let track = &mut self.tracks[track.0];
+            track.trains.push_back(train_id);
+            track.reserved_by = Some(train_id);
+        }
+
+        self.events.push(Event::LeftDepot {
+            train: train_id,
+            depot,
+        });
+
+        true
+    }
+
+    pub fn depot_ids(&self) -> impl Iterator<Item = DepotID> {
+        (0..self.depots.len()).map(DepotID)
+    }
+
+    pub fn depots(&self) -> impl Iterator<Item = DepotInfo> + '_ {
+        self.depot_ids().map(|depot| DepotInfo {
+            position: self.junctions[self.depots[depot.0].junction.0].position,
+            trains: self.stored_trains(depot).count(),
+        })
+    }
+
+    /// Trains in the depot, including the ones being serviced or waiting to
+    /// leave
+    pub fn stored_trains(&self, depot: DepotID) -> impl Iterator<Item = TrainId> + '_ {
+        self.trains
+            .iter()
+            .filter(move |train| train.location == Location::Depot(depot))
+            .map(|train| train.id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::track::generate_network;
+
+    /// Runs the network until the train has gone into the depot
+    fn run_into_depot(network: &mut Network, train: TrainId, visit: DepotVisit) {
+        network.send_to_depot(train, DepotID(0), visit).unwrap();
+
+        for _ in 0..60 * 120 {
+            network.update(1.0 / 60.0);
+            if network.trains[train.0].location == Location::Depot(DepotID(0)) {
+                return;
+            }
+        }
+        panic!("{train:?} never reached the depot");
+    }
+
+    #[test]
+    fn trains_are_serviced_in_the_depot_they_stop_at() {
+        let mut network = generate_network(0);
+        let train = network.train_ids().next().unwrap();
+
+        run_into_depot(&mut network, train, DepotVisit::Service);
+
+        assert_eq!(network.depots[0].servicing, vec![(train, SERVICE_TIME)]);
+    }
+
+    #[test]
+    fn services_that_cant_be_paid_for_are_skipped() {
+        let mut network = generate_network(0);
+        let train = network.train_ids().next().unwrap();
+        network.balance = 0.0;
+
+        run_into_depot(&mut network, train, DepotVisit::Service);
+
+        assert!(network.depots[0].servicing.is_empty());
+        assert!(network.balance <= 0.0);
+    }
+}
@@ -0,0 +1,295 @@
+use std::f32::consts::TAU;
+
+use glam::Vec2;
+
+use crate::track_shape::TrackShape;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Segment {
+    Left,
+    Straight,
+    Right,
+}
+
+/// Shortest path between two directed points made of three arcs or straights,
+/// with every arc having the same radius. Lengths are in world units.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct DubinsPath {
+    pub segments: [(Segment, f32); 3],
+    pub radius: f32,
+}
+
+const WORDS: [[Segment; 3]; 6] = [
+    [Segment::Left, Segment::Straight, Segment::Left],
+    [Segment::Right, Segment::Straight, Segment::Right],
+    [Segment::Left, Segment::Straight, Segment::Right],
+    [Segment::Right, Segment::Straight, Segment::Left],
+    [Segment::Right, Segment::Left, Segment::Right],
+    [Segment::Left, Segment::Right, Segment::Left],
+];
+
+const MIN_SEGMENT_LENGTH: f32 = 0.001;
+/// Easing a turn makes its tightest curve at least this share of the radius
+/// of the circle it replaces, whatever the angle. Small turns come closest.
+const EASED_RADIUS_RATIO: f32 = 2.0 / 3.0;
+/// How many times the radius is grown to make up for easing before falling
+/// back on the worst case
+const EASING_ATTEMPTS: usize = 4;
+/// Grows the radius a little more than needed, so rounding doesn't leave the
+/// eased curve a hair too tight
+const EASING_MARGIN: f32 = 1e-3;
+
+fn mod_tau(angle: f32) -> f32 {
+    angle.rem_euclid(TAU)
+}
+
+/// Normalized `(t, p, q)` parameters of a word, see Shkel & Lumelsky,
+/// "Classification of the Dubins set"
+fn solve_word(word: [Segment; 3], alpha: f32, beta: f32, d: f32) -> Option<[f32; 3]> {
+    let (sa, ca) = alpha.sin_cos();
+    let (sb, cb) = beta.sin_cos();
+    let c_ab = (alpha - beta).cos();
+
+    match word {
+        [Segment::Left, Segment::Straight, Segment::Left] => {
+            let p_squared = 2.0 + d * d - 2.0 * c_ab + 2.0 * d * (sa - sb);
+            if p_squared < 0.0 {
+                return None;
+            }
+            let tmp = (cb - ca).atan2(d + sa - sb);
+
+            Some([mod_tau(tmp - alpha), p_squared.sqrt(), mod_tau(beta - tmp)])
+        }
+        [Segment::Right, Segment::Straight, Segment::Right] => {
+            let p_squared = 2.0 + d * d - 2.0 * c_ab + 2.0 * d * (sb - sa);
+            if p_squared < 0.0 {
+                return None;
+            }
+            let tmp = (ca - cb).atan2(d - sa + sb);
+
+            Some([mod_tau(alpha - tmp), p_squared.sqrt(), mod_tau(tmp - beta)])
+        }
+        [Segment::Left, Segment::Straight, Segment::Right] => {
+            let p_squared = -2.0 + d * d + 2.0 * c_ab + 2.0 * d * (sa + sb);
+            if p_squared < 0.0 {
+                return None;
+            }
+            let p = p_squared.sqrt();
+            let tmp = (-ca - cb).atan2(d + sa + sb) - (-2.0f32).atan2(p);
+
+            Some([mod_tau(tmp - alpha), p, mod_tau(tmp - beta)])
+        }
+        [Segment::Right, Segment::Straight, Segment::Left] => {
+            let p_squared = -2.0 + d * d + 2.0 * c_ab - 2.0 * d * (sa + sb);
+            if p_squared < 0.0 {
+                return None;
+            }
+            let p = p_squared.sqrt();
+            let tmp = (ca + cb).atan2(d - sa - sb) - 2.0f32.atan2(p);
+
+            Some([mod_tau(alpha - tmp), p, mod_tau(beta - tmp)])
+        }
+        [Segment::Right, Segment::Left, Segment::Right] => {
+            let tmp = (6.0 - d * d + 2.0 * c_ab + 2.0 * d * (sa - sb)) / 8.0;
+            if tmp.abs() > 1.0 {
+                return None;
+            }
+            let phi = (ca - cb).atan2(d - sa + sb);
+            let p = mod_tau(TAU - tmp.acos());
+            let t = mod_tau(alpha - phi + p / 2.0);
+
+            Some([t, p, mod_tau(alpha - beta - t + p)])
+        }
+        [Segment::Left, Segment::Right, Segment::Left] => {
+            let tmp = (6.0 - d * d + 2.0 * c_ab + 2.0 * d * (sb - sa)) / 8.0;
+            if tmp.abs() > 1.0 {
+                return None;
+            }
+            let phi = (ca - cb).atan2(d + sa - sb);
+            let p = mod_tau(TAU - tmp.acos());
+            let t = mod_tau(-alpha - phi + p / 2.0);
+
+            Some([t, p, mod_tau(beta - alpha - t + p)])
+        }
+        _ => unreachable!("not a dubins word"),
+    }
+}
+
+/// There is always at least one feasible word (LSL and RSR can't both fail),
+/// so this never fails.
+pub fn shortest_path(
+    source: Vec2,
+    source_direction: Vec2,
+    destination: Vec2,
+    destination_direction: Vec2,
+    radius: f32,
+) -> DubinsPath {
+    let offset = destination - source;
+    let theta = offset.to_angle();
+    let d = offset.length() / radius;
+    let alpha = mod_tau(source_direction.to_angle() - theta);
+    let beta = mod_tau(destination_direction.to_angle() - theta);
+
+    WORDS
+        .into_iter()
+        .filter_map(|word| {
+            let [t, p, q] = solve_word(word, alpha, beta, d)?;
+
+            Some(DubinsPath {
+                segments: [
+                    (word[0], t * radius),
+                    (word[1], p * radius),
+                    (word[2], q * radius),
+                ],
+                radius,
+            })
+        })
+        .min_by(|a, b| a.length().total_cmp(&b.length()))
+        .expect("LSL or RSR is always feasible")
+}
+
+/// The shortest path as eased track shapes that curve no tighter than
+/// `turning_radius`. Easing tightens the arcs it replaces, so the path is
+/// solved again with a larger radius until its eased curves are wide enough.
+pub fn shortest_eased_path(
+    source: Vec2,
+    source_direction: Vec2,
+    destination: Vec2,
+    destination_direction: Vec2,
+    turning_radius: f32,
+) -> Vec<TrackShape> {
+    let eased = |radius| {
+        shortest_path(
+            source,
+            source_direction,
+            destination,
+            destination_direction,
+            radius,
+        )
+        .shapes(source, source_direction)
+    };
+
+    let mut radius = turning_radius;
+    for _ in 0..EASING_ATTEMPTS {
+        let shapes = eased(radius);
+        let tightest = shapes
+            .iter()
+            .filter_map(TrackShape::min_radius)
+            .fold(f32::INFINITY, f32::min);
+
+        if tightest >= turning_radius {
+            return shapes;
+        }
+
+        radius *= turning_radius / tightest * (1.0 + EASING_MARGIN);
+    }
+
+    eased(turning_radius / EASED_RADIUS_RATIO * (1.0 + EASING_MARGIN))
+}
+
+impl DubinsPath {
+    pub fn length(&self) -> f32 {
+        self.segments.iter().map(|(_, length)| length).sum()
+    }
+
+    /// The path as track shapes, with every arc replaced by an eased curve
+    /// between the same points. Segments of negligible length are left out.
+    pub fn shapes(&self, source: Vec2, source_direction: Vec2) -> Vec<TrackShape> {
+        let mut position = source;
+        let mut direction = source_direction;
+        let mut shapes = vec![];
+
+        for (segment, length) in self.segments {
+            if length < MIN_SEGMENT_LENGTH {
+                continue;
+            }
+
+            let (end, end_direction) = match segment {
+                Segment::Straight => {
+                    shapes.push(TrackShape::Line {
+                        source: position,
+                        direction,
+                        length,
+                    });
+
+                    (position + direction * length, direction)
+                }
+                Segment::Left | Segment::Right => {
+                    let sign = if segment == Segment::Left { 1.0 } else { -1.0 };
+                    let center = position + direction.perp() * sign * self.radius;
+                    let rotation = Vec2::from_angle(sign * length / self.radius);
+                    let end = center + rotation.rotate(position - center);
+
+                    shapes.extend(TrackShape::eased_turn(
+                        position,
+                        direction,
+                        end,
+                        sign * length / self.radius,
+                    ));
+
+                    (end, rotation.rotate(direction))
+                }
+            };
+
+            position = end;
+            direction = end_direction;
+        }
+
+        shapes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tightest(shapes: &[TrackShape]) -> f32 {
+        shapes
+            .iter()
+            .filter_map(TrackShape::min_radius)
+            .fold(f32::INFINITY, f32::min)
+    }
+
+    #[test]
+    fn easing_keeps_two_thirds_of_the_radius() {
+        let center = Vec2::new(0.0, 1.0);
+
+        for step in 1..200 {
+            let angle = TAU * step as f32 / 200.0;
+            let end = center + Vec2::from_angle(angle).rotate(-center);
+            let shapes = TrackShape::eased_turn(Vec2::ZERO, Vec2::X, end, angle);
+
+            assert!(
+                tightest(&shapes) >= EASED_RADIUS_RATIO - 1e-4,
+                "turning {angle} eases to {}",
+                tightest(&shapes)
+            );
+        }
+    }
+
+    #[test]
+    fn eased_paths_keep_the_turning_radius() {
+        let radius = 2.0;
+
+        for x in -4..=4 {
+            for y in -4..=4 {
+                for heading in 0..8 {
+                    let destination = Vec2::new(x as f32, y as f32) * 3.0;
+                    if destination == Vec2::ZERO {
+                        continue;
+                    }
+                    let direction = Vec2::from_angle(TAU * heading as f32 / 8.0);
+
+                    let shapes =
+                        shortest_eased_path(Vec2::ZERO, Vec2::X, destination, direction, radius);
+                    let last = shapes.last().unwrap();
+                    let (end, end_angle) = last.get_transform_at_distance(last.get_length());
+
+                    assert!(tightest(&shapes) >= radius, "to {destination} {direction}");
+                    assert!(end.distance(destination) < 0.01);
+                    assert!(Vec2::from_angle(end_angle).distance(direction) < 0.01);
+                }
+            }
+        }
+    }
+}
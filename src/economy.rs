@@ -0,0 +1,112 @@
+use crate::{
+    cargo::Crate,
+    consist::{Car, CarKind},
+    track::{Network, NetworkError, Train},
+    track_shape::TrackShape,
+};
+
+/// The money a new network has to build with
+pub(crate) const STARTING_BALANCE: f32 = 25_000.0;
+/// What a metre of straight track costs to build
+const TRACK_COST_PER_METRE: f32 = 10.0;
+/// Curves cost more the tighter they are: a metre of curve with this radius
+/// costs twice as much as a metre of straight track
+const CURVE_COST_RADIUS: f32 = 4.0;
+const LOCOMOTIVE_PRICE: f32 = 2_000.0;
+const WAGON_PRICE: f32 = 500.0;
+/// What each car costs to keep running every second
+const LOCOMOTIVE_RUNNING_COST: f32 = 0.5;
+const WAGON_RUNNING_COST: f32 = 0.1;
+/// How far a train runs before it costs twice as much to keep running, as it
+/// wears out without servicing
+const WEAR_DISTANCE: f32 = 2_000.0;
+pub(crate) const SERVICE_COST: f32 = 150.0;
+/// What a crate earns for every metre between the station it came from and
+/// the one it's delivered to
+const REVENUE_PER_METRE: f32 = 8.0;
+/// How long a crate can take before it only earns half as much
+const HALF_REVENUE_TIME: f32 = 120.0;
+
+/// What building track of the shapes costs, going by its length and how tight
+/// its curves are
+pub fn track_cost(shapes: &[TrackShape]) -> f32 {
+    shapes
+        .iter()
+        .map(|shape| {
+            let curvature = shape
+                .min_radius()
+                .map_or(0.0, |radius| CURVE_COST_RADIUS / radius);
+
+            shape.get_length() * TRACK_COST_PER_METRE * (1.0 + curvature)
+        })
+        .sum()
+}
+
+pub(crate) fn consist_price(cars: &[Car]) -> f32 {
+    cars.iter()
+        .map(|car| match car.kind {
+            CarKind::Locomotive => LOCOMOTIVE_PRICE,
+            CarKind::Wagon => WAGON_PRICE,
+        })
+        .sum()
+}
+
+impl Train {
+    /// What the train costs every second, more the longer it has gone
+    /// without servicing
+    fn running_cost(&self) -> f32 {
+        let cost: f32 = self
+            .cars
+            .iter()
+            .map(|car| match car.kind {
+                CarKind::Locomotive => LOCOMOTIVE_RUNNING_COST,
+                CarKind::Wagon => WAGON_RUNNING_COST,
+            })
+            .sum();
+
+        cost * (1.0 + self.wear / WEAR_DISTANCE)
+    }
+}
+
+impl Network {
+    pub fn balance(&self) -> f32 {
+        self.balance
+    }
+
+    /// Takes the cost from the balance, unless there isn't enough money
+    pub(crate) fn charge(&mut self, cost: f32) -> Result<(), NetworkError> {
+        if cost > self.balance {
+            return Err(NetworkError::InsufficientFunds {
+                cost,
+                balance: self.balance,
+            });
+        }
+
+        self.balance -= cost;
+
+        Ok(())
+    }
+
+    /// Running costs are paid even when there isn't enough money, so the
+    /// balance can go below zero. Trains in depots cost nothing.
+    pub(crate) fn pay_running_costs(&mut self, delta_time: f32) {
+        let cost: f32 = self
+            .trains
+            .iter()
+            .filter(|train| train.on_network())
+            .map(Train::running_cost)
+            .sum();
+        self.balance -= cost * delta_time;
+    }
+
+    /// What the crate earns when it's delivered now. Crates earn more the
+    /// further they go and less the longer they take.
+    pub(crate) fn delivery_revenue(&self, cargo: &Crate) -> f32 {
+        let distance = self.stations[cargo.origin.0]
+            .position
+            .distance(self.stations[cargo.destination.0].position);
+        let waited = self.time - cargo.created;
+
+        distance * REVENUE_PER_METRE / (1.0 + waited / HALF_REVENUE_TIME)
+    }
+}
@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+
+use crates::track::{generate_network, Event, TrainId};
+
+use crate::{orders_round_every_station, TICK};
+
+/// How long runs go for unless they're told otherwise, in seconds
+const DEFAULT_SECONDS: f32 = 600.0;
+/// How many of the busiest junctions to list
+const BUSIEST_JUNCTIONS: usize = 10;
+
+const USAGE: &str = "usage: crates --headless [--seconds N] [--seed N]";
+
+/// Runs a generated network for a while without a window, with every train
+/// going round every station, then prints what happened
+pub fn run(mut args: impl Iterator<Item = String>) {
+    let mut seconds = DEFAULT_SECONDS;
+    let mut seed = None;
+
+    while let Some(arg) = args.next() {
+        let value = args.next();
+        let parsed = match arg.as_str() {
+            "--seconds" => value
+                .and_then(|value| value.parse::<f32>().ok())
+                .filter(|value| value.is_finite() && *value > 0.0)
+                .map(|value| seconds = value),
+            "--seed" => value
+                .and_then(|value| value.parse().ok())
+                .map(|value| seed = Some(value)),
+            _ => None,
+        };
+
+        if parsed.is_none() {
+            eprintln!("{USAGE}");
+            std::process::exit(2);
+        }
+    }
+
+    let seed = seed.unwrap_or_else(rand::random);
+    let mut network = generate_network(seed);
+    let orders = orders_round_every_station(&network);
+    let trains: Vec<_> = network.train_ids().collect();
+    for &train in &trains {
+        network.set_orders(train, orders.clone()).unwrap();
+    }
+
+    let mut crashes = 0;
+    let mut deliveries: HashMap<TrainId, usize> = HashMap::new();
+    // Each violation is only reported the first time it's seen
+    let mut violations = vec![];
+
+    let ticks = (seconds / TICK).round() as usize;
+    for tick in 0..ticks {
+        network.update(TICK);
+
+        for event in network.drain_events() {
+            match event {
+                Event::Crash { .. } => crashes += 1,
+                Event::CrateDelivered { train, .. } => *deliveries.entry(train).or_default() += 1,
+                _ => {}
+            }
+        }
+
+        for violation in network.invariant_violations() {
+            if !violations.iter().any(|(_, seen)| *seen == violation) {
+                violations.push((tick as f32 * TICK, violation));
+            }
+        }
+    }
+
+    println!("seed {seed}");
+    println!("simulated {seconds}s");
+    println!("balance {:.0}", network.balance());
+
+    println!("trains:");
+    for train in network.trains() {
+        println!(
+            "  {:?}: {:.0}m travelled, {} crates delivered",
+            train.id,
+            train.travelled,
+            deliveries.get(&train.id).copied().unwrap_or(0)
+        );
+    }
+
+    let mut visits: Vec<_> = network.junction_visits().collect();
+    visits.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+    println!("busiest junctions:");
+    for (junction, count) in visits.into_iter().take(BUSIEST_JUNCTIONS) {
+        println!("  {junction:?}: {count} visits");
+    }
+
+    println!("crashes {crashes}");
+    println!("invariant violations {}", violations.len());
+    for (time, violation) in violations {
+        println!("  at {time:.2}s: {violation}");
+    }
+}
@@ -0,0 +1,98 @@
+use std::fmt;
+
+use crate::track::{Network, TrackID, TrainId};
+
+/// Something about the state of the network that should never happen
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Violation {
+    /// The front of the train is past either end of its track
+    OffTrack {
+        train: TrainId,
+        track: TrackID,
+        distance: f32,
+    },
+    /// The train covers the track, but the track doesn't know
+    NotOnTrack { train: TrainId, track: TrackID },
+    /// The track has the train on it, but the train doesn't cover it
+    StrayTrain { train: TrainId, track: TrackID },
+    /// The train is on a track another train has reserved
+    OnReservedTrack {
+        train: TrainId,
+        track: TrackID,
+        reserved_by: TrainId,
+    },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::OffTrack {
+                train,
+                track,
+                distance,
+            } => write!(f, "{train:?} is {distance} along {track:?}, off its end"),
+            Violation::NotOnTrack { train, track } => {
+                write!(f, "{train:?} covers {track:?} without being on it")
+            }
+            Violation::StrayTrain { train, track } => {
+                write!(f, "{track:?} has {train:?} on it, which doesn't cover it")
+            }
+            Violation::OnReservedTrack {
+                train,
+                track,
+                reserved_by,
+            } => write!(f, "{train:?} is on {track:?}, reserved by {reserved_by:?}"),
+        }
+    }
+}
+
+impl Network {
+    /// Checks that trains and the tracks they're on agree with each other
+    pub fn invariant_violations(&self) -> Vec<Violation> {
+        let mut violations = vec![];
+
+        for train in self.trains.iter().filter(|train| train.on_network()) {
+            let length = self.tracks[train.track.0].length;
+            if !(0.0..=length + 1e-3).contains(&train.distance) {
+                violations.push(Violation::OffTrack {
+                    train: train.id,
+                    track: train.track,
+                    distance: train.distance,
+                });
+            }
+
+            for track in std::iter::once(&train.track).chain(&train.trailing) {
+                if !self.tracks[track.0].trains.contains(&train.id) {
+                    violations.push(Violation::NotOnTrack {
+                        train: train.id,
+                        track: *track,
+                    });
+                }
+            }
+        }
+
+        for track in &self.tracks {
+            for train_id in &track.trains {
+                let train = &self.trains[train_id.0];
+                let covers = train.on_network()
+                    && (train.track == track.id || train.trailing.contains(&track.id));
+                if !covers {
+                    violations.push(Violation::StrayTrain {
+                        train: *train_id,
+                        track: track.id,
+                    });
+                }
+
+                if let Some(reserved_by) = track.reserved_by.filter(|by| by != train_id) {
+                    violations.push(Violation::OnReservedTrack {
+                        train: *train_id,
+                        track: track.id,
+                        reserved_by,
+                    });
+                }
+            }
+        }
+
+        violations
+    }
+}
@@ -0,0 +1,20 @@
+//! A train simulation: track built from junctions, signals, stations and
+//! depots, and trains running on it. Drawing needs the `render` feature.
+
+pub mod cargo;
+mod collision;
+pub mod consist;
+pub mod depot;
+mod dubins;
+pub mod economy;
+pub mod invariants;
+pub mod minivec;
+pub mod orders;
+pub mod physics;
+#[cfg(feature = "render")]
+pub mod proper_draw_arc;
+mod routing;
+pub mod save;
+pub mod signal;
+pub mod track;
+pub mod track_shape;
@@ -1,5 +1,7 @@
 use std::ops::Index;
 
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub struct Minivec<const SIZE: usize, T: Default + Copy> {
     length: usize,
@@ -15,7 +17,15 @@ impl<const SIZE: usize, T: Default + Copy> Minivec<SIZE, T> {
     }
 
     pub fn len(&self) -> usize {
-        return self.length;
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.length >= SIZE
     }
 
     pub fn push(&mut self, item: T) -> Result<(), T> {
@@ -29,6 +39,12 @@ impl<const SIZE: usize, T: Default + Copy> Minivec<SIZE, T> {
     }
 }
 
+impl<const SIZE: usize, T: Default + Copy> Default for Minivec<SIZE, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<'a, const SIZE: usize, T: Default + Copy> IntoIterator for &'a Minivec<SIZE, T> {
     type Item = &'a T;
 
@@ -71,3 +87,26 @@ impl<'a, const SIZE: usize, T: Default + Copy> Iterator for MiniVecIterator<'a,
         }
     }
 }
+
+impl<const SIZE: usize, T: Default + Copy + Serialize> Serialize for Minivec<SIZE, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self)
+    }
+}
+
+impl<'de, const SIZE: usize, T: Default + Copy + Deserialize<'de>> Deserialize<'de>
+    for Minivec<SIZE, T>
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let items = Vec::<T>::deserialize(deserializer)?;
+        let mut minivec = Self::new();
+
+        for item in &items {
+            minivec.push(*item).map_err(|_| {
+                D::Error::invalid_length(items.len(), &format!("at most {SIZE}").as_str())
+            })?;
+        }
+
+        Ok(minivec)
+    }
+}
@@ -0,0 +1,394 @@
+use serde::{Deserialize, Serialize};
+
+use crate::track::{
+    Destination, Event, JunctionId, Network, NetworkError, StationID, Train, TrainId,
+};
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Order {
+    /// Go to the station and stop there
+    GoTo(StationID),
+    /// Pass through the junction on the way to the next station
+    Via(JunctionId),
+    /// Stay at the station for this many seconds
+    Wait(f32),
+    /// Load the waiting crates for stations the train goes to
+    Load,
+    /// Unload the crates for the station
+    Unload,
+}
+
+/// When a train is due to leave the stations its orders take it to, one
+/// departure for every `GoTo` order in turn. Departures are in seconds into a
+/// period that starts again every time the train goes through its orders.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Timetable {
+    pub period: f32,
+    pub departures: Vec<f32>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Schedule {
+    pub(crate) timetable: Timetable,
+    /// When the current run through the orders started, going by the
+    /// timetable
+    start: f32,
+}
+
+impl Timetable {
+    /// Checks there's a departure for every station the orders go to, each
+    /// within a period that can repeat
+    pub(crate) fn check(&self, orders: &[Order]) -> Result<(), NetworkError> {
+        if !(self.period.is_finite() && self.period > 0.0) {
+            return Err(NetworkError::InvalidPeriod(self.period));
+        }
+        if let Some(departure) =
+            (self.departures.iter()).find(|departure| !(0.0..self.period).contains(*departure))
+        {
+            return Err(NetworkError::DepartureOutsidePeriod {
+                departure: *departure,
+                period: self.period,
+            });
+        }
+
+        let stops = orders
+            .iter()
+            .filter(|order| matches!(order, Order::GoTo(_)))
+            .count();
+
+        if self.departures.len() != stops {
+            return Err(NetworkError::TimetableMismatch {
+                stops,
+                departures: self.departures.len(),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+impl Train {
+    fn next_order(&mut self) {
+        self.order += 1;
+
+        if self.order == self.orders.len() {
+            self.order = 0;
+            if let Some(schedule) = &mut self.schedule {
+                schedule.start += schedule.timetable.period;
+            }
+        }
+    }
+
+    /// Whether the train has an order to go to the station
+    pub(crate) fn serves(&self, station: StationID) -> bool {
+        self.orders.contains(&Order::GoTo(station))
+    }
+}
+
+impl Network {
+    /// Gives the train orders to carry out in turn, starting over after the
+    /// last one. Any timetable the train kept is dropped.
+    pub fn set_orders(
+        &mut self,
+        train_id: TrainId,
+        orders: Vec<Order>,
+    ) -> Result<(), NetworkError> {
+        self.train(train_id)?;
+        self.check_orders(&orders)?;
+
+        let train = &mut self.trains[train_id.0];
+        train.orders = orders;
+        train.order = 0;
+        train.schedule = None;
+        train.departure_due = None;
+        train.destination = None;
+        train.route.clear();
+
+        Ok(())
+    }
+
+    /// Checks the stations and junctions the orders go to are all there
+    pub(crate) fn check_orders(&self, orders: &[Order]) -> Result<(), NetworkError> {
+        for order in orders {
+            match *order {
+                Order::GoTo(station) if station.0 >= self.stations.len() => {
+                    return Err(NetworkError::UnknownStation(station));
+                }
+                Order::Via(junction) => {
+                    self.junction(junction)?;
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Makes the train keep to the timetable, with its first period starting
+    /// now, or run as soon as its orders let it with `None`
+    pub fn set_timetable(
+        &mut self,
+        train_id: TrainId,
+        timetable: Option<Timetable>,
+    ) -> Result<(), NetworkError> {
+        self.train(train_id)?;
+        let train = &mut self.trains[train_id.0];
+
+        if let Some(timetable) = &timetable {
+            timetable.check(&train.orders)?;
+        }
+
+        train.schedule = timetable.map(|timetable| Schedule {
+            timetable,
+            start: self.time,
+        });
+
+        Ok(())
+    }
+
+    /// Ends the `GoTo` order for the station the train has stopped at
+    pub(crate) fn stop_for_orders(&mut self, train_id: TrainId, station: StationID) {
+        let train = &mut self.trains[train_id.0];
+        train.dwelling = Some((station, 0.0));
+        train.destination = None;
+        train.route.clear();
+
+        // Stopped here on the way to somewhere else
+        if train.orders.get(train.order) != Some(&Order::GoTo(station)) {
+            return;
+        }
+
+        let stop = train.orders[..train.order]
+            .iter()
+            .filter(|order| matches!(order, Order::GoTo(_)))
+            .count();
+        train.departure_due = train
+            .schedule
+            .as_ref()
+            .map(|schedule| schedule.start + schedule.timetable.departures[stop]);
+        train.next_order();
+    }
+
+    /// Carries out the train's orders up to the next one that takes time.
+    /// Returns whether the train may move, which it may not while its orders
+    /// keep it at a station.
+    pub(crate) fn follow_orders(&mut self, train_id: TrainId) -> bool {
+        let train = &self.trains[train_id.0];
+        // Trains finish getting where they're going before the next order
+        if train.dwelling.is_none() && train.destination.is_some() {
+            return true;
+        }
+
+        for _ in 0..train.orders.len() {
+            let train = &mut self.trains[train_id.0];
+            let station = train.dwelling.map(|(station, _)| station);
+
+            // Orders for stations are skipped by trains that aren't at one
+            match (train.orders[train.order], station) {
+                (Order::GoTo(_) | Order::Via(_), _) => return self.head_for_next_stop(train_id),
+                (Order::Wait(seconds), Some(station)) => {
+                    train.dwelling = Some((station, seconds));
+                    train.next_order();
+                    return false;
+                }
+                (Order::Load, Some(station)) => self.load_cargo(train_id, station),
+                (Order::Unload, Some(station)) => self.unload_cargo(train_id, station),
+                (Order::Wait(_) | Order::Load | Order::Unload, None) => {}
+            }
+
+            self.trains[train_id.0].next_order();
+        }
+
+        true
+    }
+
+    /// Leaves for the station of the train's next `GoTo` order, through the
+    /// junctions of the `Via` orders before it, once the timetable lets it.
+    /// Trains that can't get there from where they are go on anyway and try
+    /// again as they go.
+    fn head_for_next_stop(&mut self, train_id: TrainId) -> bool {
+        let train = &self.trains[train_id.0];
+        if train.departure_due.is_some_and(|due| due > self.time) {
+            return false;
+        }
+
+        let orders = train.orders.len();
+        let mut vias: Vec<_> = (0..orders)
+            .map(|index| train.orders[(train.order + index) % orders])
+            .map_while(|order| match order {
+                Order::Via(junction) => Some(junction),
+                _ => None,
+            })
+            .collect();
+        let passed = vias.len();
+        let destination = match train.orders[(train.order + passed) % orders] {
+            Order::GoTo(station) => Destination::Station(station),
+            // Nowhere to stop after the junctions, so the last one will do
+            _ => Destination::Junction(vias.pop().unwrap()),
+        };
+
+        let route = vias
+            .into_iter()
+            .map(Destination::Junction)
+            .chain(std::iter::once(destination))
+            .try_fold(vec![], |mut route, leg| {
+                let from = route.last().copied().unwrap_or(train.track);
+                route.extend(self.plan_route(from, train.backwards, leg)?);
+                Ok::<_, NetworkError>(route)
+            });
+
+        let train = &mut self.trains[train_id.0];
+        if let Some((station, _)) = train.dwelling.take() {
+            self.events.push(Event::DepartedStation {
+                train: train_id,
+                station,
+                lateness: train.departure_due.take().map(|due| self.time - due),
+            });
+        }
+
+        if let Ok(route) = route {
+            train.destination = Some(destination);
+            train.route = route.into();
+            // A `GoTo` order lasts until the train stops at the station
+            for _ in 0..passed {
+                train.next_order();
+            }
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::track::generate_network;
+
+    fn timetable(period: f32, departures: &[f32]) -> Timetable {
+        Timetable {
+            period,
+            departures: departures.to_vec(),
+        }
+    }
+
+    /// The generated network with its first train going between both stations
+    fn network_with_orders(orders: Vec<Order>) -> (Network, TrainId) {
+        let mut network = generate_network(0);
+        let train = network.train_ids().next().unwrap();
+        network.set_orders(train, orders).unwrap();
+
+        (network, train)
+    }
+
+    #[test]
+    fn timetables_must_fit_their_period() {
+        let orders = [Order::GoTo(StationID(0)), Order::GoTo(StationID(1))];
+
+        for period in [0.0, -10.0, f32::NAN, f32::INFINITY] {
+            assert!(matches!(
+                timetable(period, &[0.0, 1.0]).check(&orders),
+                Err(NetworkError::InvalidPeriod(_))
+            ));
+        }
+        for departure in [-1.0, 100.0, 150.0, f32::NAN] {
+            assert!(matches!(
+                timetable(100.0, &[0.0, departure]).check(&orders),
+                Err(NetworkError::DepartureOutsidePeriod { .. })
+            ));
+        }
+        assert_eq!(
+            timetable(100.0, &[0.0]).check(&orders),
+            Err(NetworkError::TimetableMismatch {
+                stops: 2,
+                departures: 1
+            })
+        );
+        assert_eq!(timetable(100.0, &[0.0, 99.0]).check(&orders), Ok(()));
+    }
+
+    #[test]
+    fn orders_start_over_in_the_next_period() {
+        let (mut network, train) =
+            network_with_orders(vec![Order::GoTo(StationID(0)), Order::GoTo(StationID(1))]);
+        network.time = 5.0;
+        network
+            .set_timetable(train, Some(timetable(100.0, &[10.0, 60.0])))
+            .unwrap();
+
+        let train = &mut network.trains[train.0];
+        train.next_order();
+        assert_eq!(train.order, 1);
+        assert_eq!(train.schedule.as_ref().unwrap().start, 5.0);
+
+        train.next_order();
+        assert_eq!(train.order, 0);
+        assert_eq!(train.schedule.as_ref().unwrap().start, 105.0);
+    }
+
+    #[test]
+    fn trains_wait_for_their_departure_and_report_lateness() {
+        let (mut network, train) =
+            network_with_orders(vec![Order::GoTo(StationID(0)), Order::GoTo(StationID(1))]);
+        network
+            .set_timetable(train, Some(timetable(600.0, &[200.0, 400.0])))
+            .unwrap();
+
+        let mut departures = vec![];
+        while network.time < 500.0 {
+            network.update(1.0 / 60.0);
+            let time = network.time;
+            departures.extend(network.drain_events().filter_map(|event| match event {
+                Event::DepartedStation {
+                    train: departed,
+                    station,
+                    lateness,
+                } if departed == train => Some((time, station, lateness)),
+                _ => None,
+            }));
+        }
+
+        assert_eq!(departures.len(), 2);
+        for ((time, station, lateness), (due, expected)) in
+            departures.into_iter().zip([(200.0, 0), (400.0, 1)])
+        {
+            assert_eq!(station, StationID(expected));
+            let lateness = lateness.unwrap();
+            assert!((0.0..0.1).contains(&lateness), "{lateness}s late");
+            assert!((time - due - lateness).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn trains_go_through_via_junctions_in_order() {
+        let vias = [JunctionId(5), JunctionId(8)];
+        let (mut network, train) = network_with_orders(vec![
+            Order::Via(vias[0]),
+            Order::Via(vias[1]),
+            Order::GoTo(StationID(0)),
+        ]);
+
+        assert!(network.follow_orders(train));
+
+        let train = &network.trains[train.0];
+        assert_eq!(train.order, 2);
+        assert_eq!(train.destination, Some(Destination::Station(StationID(0))));
+        let passed: Vec<_> = (train.route.iter())
+            .map(|track| network.tracks[track.0].end(train.backwards))
+            .filter(|junction| vias.contains(junction))
+            .collect();
+        assert_eq!(passed, vias);
+    }
+
+    #[test]
+    fn vias_without_a_station_stop_at_the_last_junction() {
+        let via = JunctionId(5);
+        let (mut network, train) = network_with_orders(vec![Order::Via(via)]);
+
+        network.follow_orders(train);
+
+        assert_eq!(
+            network.trains[train.0].destination,
+            Some(Destination::Junction(via))
+        );
+    }
+}
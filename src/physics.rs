@@ -0,0 +1,190 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    consist::CarKind,
+    track::{Network, NetworkError, Train, TrainId},
+    track_shape::TrackShape,
+};
+
+/// How hard trains may be pushed sideways in curves, in metres per second
+/// squared
+const MAX_LATERAL_ACCELERATION: f32 = 1.5;
+/// Of an empty wagon, in tonnes
+const WAGON_MASS: f32 = 15.0;
+/// In tonnes
+const CRATE_MASS: f32 = 5.0;
+
+/// How a train speeds up and slows down. Distances are in metres, so speeds
+/// are in metres per second.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TrainPhysics {
+    /// Of the locomotive, in tonnes
+    pub mass: f32,
+    /// The most force the train can pull with, in kilonewtons
+    pub tractive_effort: f32,
+    /// How quickly the train slows down when braking, in metres per second
+    /// squared
+    pub braking: f32,
+    pub top_speed: f32,
+}
+
+impl Default for TrainPhysics {
+    fn default() -> Self {
+        TrainPhysics {
+            mass: 60.0,
+            tractive_effort: 90.0,
+            braking: 1.5,
+            top_speed: 8.0,
+        }
+    }
+}
+
+impl TrainPhysics {
+    /// Whether every value is finite and positive, which trains need to ever
+    /// move or stop
+    pub fn is_valid(&self) -> bool {
+        [
+            self.mass,
+            self.tractive_effort,
+            self.braking,
+            self.top_speed,
+        ]
+        .iter()
+        .all(|value| value.is_finite() && *value > 0.0)
+    }
+
+    /// How quickly the locomotive speeds up pulling `mass` tonnes, itself
+    /// included
+    pub fn acceleration(&self, mass: f32) -> f32 {
+        self.tractive_effort / mass
+    }
+
+    /// How far the train goes before coming to a halt when braking from `speed`
+    pub fn braking_distance(&self, speed: f32) -> f32 {
+        speed * speed / (2.0 * self.braking)
+    }
+
+    /// The fastest the train can go and still slow down to `target` within
+    /// `distance`
+    pub fn approach_speed(&self, distance: f32, target: f32) -> f32 {
+        (target * target + 2.0 * self.braking * distance).sqrt()
+    }
+}
+
+/// The fastest trains may go along the shape, `None` if it is straight
+pub fn curve_speed_limit(shape: &TrackShape) -> Option<f32> {
+    shape
+        .min_radius()
+        .map(|radius| (MAX_LATERAL_ACCELERATION * radius).sqrt())
+}
+
+impl Train {
+    /// Of the whole train with its cargo, in tonnes
+    pub(crate) fn mass(&self) -> f32 {
+        let wagons = self
+            .cars
+            .iter()
+            .filter(|car| car.kind == CarKind::Wagon)
+            .count();
+
+        self.physics.mass + wagons as f32 * WAGON_MASS + self.cargo.len() as f32 * CRATE_MASS
+    }
+}
+
+impl Network {
+    pub fn set_train_physics(
+        &mut self,
+        train: TrainId,
+        physics: TrainPhysics,
+    ) -> Result<(), NetworkError> {
+        self.train(train)?;
+        if !physics.is_valid() {
+            return Err(NetworkError::InvalidPhysics(physics));
+        }
+        self.trains[train.0].physics = physics;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cargo::Crate,
+        track::{generate_network, StationID},
+    };
+
+    #[test]
+    fn physics_must_be_finite_and_positive() {
+        let mut network = generate_network(0);
+        let train = network.train_ids().next().unwrap();
+
+        for physics in [
+            TrainPhysics {
+                braking: 0.0,
+                ..Default::default()
+            },
+            TrainPhysics {
+                mass: -60.0,
+                ..Default::default()
+            },
+            TrainPhysics {
+                top_speed: f32::INFINITY,
+                ..Default::default()
+            },
+            TrainPhysics {
+                tractive_effort: f32::NAN,
+                ..Default::default()
+            },
+        ] {
+            assert!(matches!(
+                network.set_train_physics(train, physics),
+                Err(NetworkError::InvalidPhysics(_))
+            ));
+        }
+        assert_eq!(
+            network.set_train_physics(train, TrainPhysics::default()),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn wagons_and_cargo_slow_acceleration() {
+        let mut network = generate_network(0);
+        let train = &mut network.trains[0];
+        let empty = train.mass();
+        let crates = train.capacity();
+        train.cargo = vec![
+            Crate {
+                origin: StationID(0),
+                destination: StationID(1),
+                created: 0.0,
+            };
+            crates
+        ];
+
+        assert_eq!(empty, train.physics.mass + 2.0 * WAGON_MASS);
+        assert_eq!(train.mass(), empty + crates as f32 * CRATE_MASS);
+        assert!(train.physics.acceleration(train.mass()) < train.physics.acceleration(empty));
+    }
+
+    #[test]
+    fn weak_brakes_only_look_so_far_ahead() {
+        let mut network = generate_network(0);
+        // The first train waits at a signal for the second one
+        let train = network.train_ids().nth(1).unwrap();
+        let physics = TrainPhysics {
+            braking: 1e-6,
+            ..Default::default()
+        };
+        network.set_train_physics(train, physics).unwrap();
+
+        for _ in 0..60 {
+            network.update(1.0 / 60.0);
+        }
+
+        assert!(network.trains[train.0].travelled > 0.0);
+        assert_eq!(network.invariant_violations(), vec![]);
+    }
+}
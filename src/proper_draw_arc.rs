@@ -1,7 +1,5 @@
-use std::f32::consts::TAU;
-
 use glam::Vec2;
-use macroquad::{color::Color, shapes::draw_line};
+use macroquad::color::Color;
 
 pub fn draw_arc(
     center: Vec2,
@@ -0,0 +1,126 @@
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap},
+    hash::Hash,
+};
+
+struct Candidate<N> {
+    cost: f32,
+    node: N,
+}
+
+impl<N> PartialEq for Candidate<N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cost.total_cmp(&other.cost) == Ordering::Equal
+    }
+}
+
+impl<N> Eq for Candidate<N> {}
+
+impl<N> PartialOrd for Candidate<N> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<N> Ord for Candidate<N> {
+    // Reversed, so the binary heap pops the cheapest candidate first
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.total_cmp(&self.cost)
+    }
+}
+
+/// Dijkstra's algorithm. Returns the nodes after `start` up to and including
+/// the first goal reached, or an empty path if `start` is a goal itself.
+pub fn shortest_path<N, I>(
+    start: N,
+    successors: impl Fn(N) -> I,
+    is_goal: impl Fn(N) -> bool,
+) -> Option<Vec<N>>
+where
+    N: Copy + Eq + Hash,
+    I: IntoIterator<Item = (N, f32)>,
+{
+    let mut previous: HashMap<N, Option<N>> = HashMap::new();
+    let mut costs: HashMap<N, f32> = HashMap::new();
+    let mut queue = BinaryHeap::new();
+
+    previous.insert(start, None);
+    costs.insert(start, 0.0);
+    queue.push(Candidate {
+        cost: 0.0,
+        node: start,
+    });
+
+    while let Some(Candidate { cost, node }) = queue.pop() {
+        if cost > costs[&node] {
+            continue;
+        }
+
+        if is_goal(node) {
+            let mut path = vec![];
+            let mut current = node;
+            while let Some(before) = previous[&current] {
+                path.push(current);
+                current = before;
+            }
+            path.reverse();
+
+            return Some(path);
+        }
+
+        for (next, step_cost) in successors(node) {
+            let next_cost = cost + step_cost;
+
+            if costs.get(&next).is_none_or(|known| next_cost < *known) {
+                costs.insert(next, next_cost);
+                previous.insert(next, Some(node));
+                queue.push(Candidate {
+                    cost: next_cost,
+                    node: next,
+                });
+            }
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 0 -> 1 -> 3 costs 2 + 2, 0 -> 2 -> 3 costs 1 + 5, 4 is cut off
+    fn edges(node: u32) -> Vec<(u32, f32)> {
+        match node {
+            0 => vec![(1, 2.0), (2, 1.0)],
+            1 => vec![(3, 2.0)],
+            2 => vec![(3, 5.0)],
+            3 => vec![(0, 1.0)],
+            _ => vec![],
+        }
+    }
+
+    #[test]
+    fn finds_the_cheapest_path_not_the_fewest_steps_first() {
+        assert_eq!(shortest_path(0, edges, |node| node == 3), Some(vec![1, 3]));
+    }
+
+    #[test]
+    fn start_at_goal_is_an_empty_path() {
+        assert_eq!(shortest_path(2, edges, |node| node == 2), Some(vec![]));
+    }
+
+    #[test]
+    fn unreachable_goal_is_none() {
+        assert_eq!(shortest_path(0, edges, |node| node == 4), None);
+    }
+
+    #[test]
+    fn stops_at_the_nearest_of_several_goals() {
+        assert_eq!(
+            shortest_path(0, edges, |node| node == 1 || node == 3),
+            Some(vec![1])
+        );
+    }
+}
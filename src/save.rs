@@ -0,0 +1,491 @@
+use std::fmt;
+
+use ron::{error::SpannedError, ser::PrettyConfig};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cargo::Crate,
+    consist::valid_car_length,
+    depot::{DepotID, Location},
+    invariants::Violation,
+    physics::curve_speed_limit,
+    track::{
+        check_turning_radius, path_fits, Destination, Network, NetworkError, StationID, TrackID,
+        TrainId,
+    },
+};
+
+/// The version of the format networks are saved in. It goes up whenever saves
+/// from before can't be read the same way anymore.
+pub const SAVE_VERSION: u32 = 1;
+
+#[derive(Debug)]
+pub enum LoadError {
+    /// The text isn't a saved network
+    Format(SpannedError),
+    /// The network was saved in a version of the format this one can't read
+    UnsupportedVersion(u32),
+    /// Something refers to a junction, track, train, station or depot that
+    /// isn't there, a timetable doesn't fit its train's orders or a train's
+    /// physics are impossible
+    Invalid(NetworkError),
+    /// Something is out of place, like a switch set to a track its junction
+    /// doesn't have or a train without any cars
+    Corrupt(&'static str),
+    /// Trains and tracks disagree about where the trains are
+    Inconsistent(Violation),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Format(error) => write!(f, "not a saved network: {error}"),
+            LoadError::UnsupportedVersion(version) => write!(
+                f,
+                "saved in version {version} of the format, only version {SAVE_VERSION} can be read"
+            ),
+            LoadError::Invalid(error) => write!(f, "invalid network: {error}"),
+            LoadError::Corrupt(problem) => write!(f, "corrupt network: {problem}"),
+            LoadError::Inconsistent(violation) => write!(f, "inconsistent network: {violation}"),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+impl From<SpannedError> for LoadError {
+    fn from(error: SpannedError) -> Self {
+        LoadError::Format(error)
+    }
+}
+
+impl From<NetworkError> for LoadError {
+    fn from(error: NetworkError) -> Self {
+        LoadError::Invalid(error)
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename = "Save")]
+struct SaveFile<'a> {
+    version: u32,
+    network: &'a Network,
+}
+
+/// Just enough of a save to tell which version of the format it's in, before
+/// trying to read the rest
+#[derive(Deserialize)]
+#[serde(rename = "Save")]
+struct Header {
+    version: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(rename = "Save")]
+struct LoadFile {
+    network: Network,
+}
+
+impl Network {
+    /// Writes out everything needed to carry on from where the network is now,
+    /// apart from events that haven't been drained
+    pub fn save(&self) -> String {
+        let file = SaveFile {
+            version: SAVE_VERSION,
+            network: self,
+        };
+
+        ron::ser::to_string_pretty(&file, PrettyConfig::new().struct_names(true))
+            .expect("networks can always be saved")
+    }
+
+    /// Reads a network written by `save`, checking it hangs together before
+    /// anything is simulated with it
+    pub fn load(text: &str) -> Result<Network, LoadError> {
+        let header: Header = ron::from_str(text)?;
+        if header.version != SAVE_VERSION {
+            return Err(LoadError::UnsupportedVersion(header.version));
+        }
+
+        let mut network = ron::from_str::<LoadFile>(text)?.network;
+        network.check_references()?;
+        network.rebuild_blocks();
+
+        if let Some(violation) = network.invariant_violations().first() {
+            return Err(LoadError::Inconsistent(*violation));
+        }
+
+        Ok(network)
+    }
+
+    /// Checks everything in a loaded network refers to things that are there,
+    /// that tracks and junctions agree on how they're connected and that
+    /// nothing is where or how it couldn't be
+    fn check_references(&self) -> Result<(), LoadError> {
+        let track = |id: TrackID| {
+            self.tracks
+                .get(id.0)
+                .map(|_| ())
+                .ok_or(NetworkError::UnknownTrack(id))
+        };
+        let train = |id: TrainId| {
+            self.trains
+                .get(id.0)
+                .map(|_| ())
+                .ok_or(NetworkError::UnknownTrain(id))
+        };
+        let station = |id: StationID| {
+            self.stations
+                .get(id.0)
+                .map(|_| ())
+                .ok_or(NetworkError::UnknownStation(id))
+        };
+        let depot = |id: DepotID| {
+            self.depots
+                .get(id.0)
+                .map(|_| ())
+                .ok_or(NetworkError::UnknownDepot(id))
+        };
+        let cargo = |cargo: &Crate| station(cargo.origin).and(station(cargo.destination));
+
+        if !(self.balance.is_finite() && self.time.is_finite()) {
+            return Err(LoadError::Corrupt("the balance or time isn't a number"));
+        }
+        check_turning_radius(self.turning_radius)?;
+
+        for (index, junction) in self.junctions.iter().enumerate() {
+            if junction.id.0 != index {
+                return Err(LoadError::Corrupt("junctions are out of order"));
+            }
+            if !junction.position.is_finite() {
+                return Err(LoadError::Corrupt("a junction isn't anywhere"));
+            }
+
+            for (tracks, end) in [(&junction.enterances, true), (&junction.exits, false)] {
+                for id in tracks {
+                    track(*id)?;
+                    if self.tracks[id.0].end(!end) != junction.id {
+                        return Err(NetworkError::NotAtJunction {
+                            junction: junction.id,
+                            track: *id,
+                        }
+                        .into());
+                    }
+                }
+            }
+
+            if junction.switch >= junction.exits.len().max(1)
+                || junction.enterance_switch >= junction.enterances.len().max(1)
+            {
+                return Err(LoadError::Corrupt(
+                    "a switch is set to a track its junction doesn't have",
+                ));
+            }
+
+            if junction
+                .signal
+                .is_some_and(|signal| signal.0 >= self.signals.len())
+            {
+                return Err(LoadError::Corrupt(
+                    "a junction has a signal that isn't there",
+                ));
+            }
+        }
+
+        for (index, checked) in self.tracks.iter().enumerate() {
+            if checked.id.0 != index {
+                return Err(LoadError::Corrupt("tracks are out of order"));
+            }
+
+            let source = self.junction(checked.source)?;
+            let destination = self.junction(checked.destiation)?;
+            if !path_fits(&[checked.shape], source.position, destination.position) {
+                return Err(LoadError::Corrupt(
+                    "a track's shape doesn't go from one of its junctions to the other",
+                ));
+            }
+            // What's worked out from the shape has to be what the shape gives
+            let shape_limit = curve_speed_limit(&checked.shape);
+            let speed_limits_match = match (checked.speed_limit, shape_limit) {
+                (Some(saved), Some(shape)) => (saved - shape).abs() <= 1e-3,
+                (saved, shape) => saved.is_none() && shape.is_none(),
+            };
+            if (checked.length - checked.shape.get_length()).abs() > 1e-3 || !speed_limits_match {
+                return Err(LoadError::Corrupt(
+                    "a track's length or speed limit doesn't match its shape",
+                ));
+            }
+            for (junction, tracks) in [
+                (source, &source.exits),
+                (destination, &destination.enterances),
+            ] {
+                if !tracks.into_iter().any(|id| *id == checked.id) {
+                    return Err(NetworkError::NotAtJunction {
+                        junction: junction.id,
+                        track: checked.id,
+                    }
+                    .into());
+                }
+            }
+
+            checked.trains.iter().try_for_each(|id| train(*id))?;
+            checked.reserved_by.map_or(Ok(()), train)?;
+            checked.conflicts.iter().try_for_each(|id| track(*id))?;
+        }
+
+        for signal in &self.signals {
+            self.junction(signal.junction)?;
+        }
+
+        for station in &self.stations {
+            if !(station.dwell_time.is_finite() && station.dwell_time >= 0.0) {
+                return Err(NetworkError::InvalidDwellTime(station.dwell_time).into());
+            }
+            let rate = station.production.rate;
+            if !(rate.is_finite() && rate >= 0.0) {
+                return Err(NetworkError::InvalidProduction(rate).into());
+            }
+            track(station.track)?;
+            self.junction(station.end)?;
+            station.production.waiting.iter().try_for_each(cargo)?;
+        }
+
+        for depot in &self.depots {
+            self.junction(depot.junction)?;
+            depot.departures.iter().try_for_each(|id| train(*id))?;
+            depot.servicing.iter().try_for_each(|(id, _)| train(*id))?;
+        }
+
+        for (index, train) in self.trains.iter().enumerate() {
+            if train.id.0 != index {
+                return Err(LoadError::Corrupt("trains are out of order"));
+            }
+
+            if train.cars.is_empty() || !train.cars.iter().all(|car| valid_car_length(car.length)) {
+                return Err(LoadError::Corrupt("a train has a missing or empty car"));
+            }
+            if train.cargo.len() > train.capacity() {
+                return Err(LoadError::Corrupt(
+                    "a train carries more crates than its wagons hold",
+                ));
+            }
+            if !train.physics.is_valid() {
+                return Err(NetworkError::InvalidPhysics(train.physics).into());
+            }
+            if !(train.speed.is_finite() && train.speed >= 0.0) {
+                return Err(LoadError::Corrupt(
+                    "a train is going at an impossible speed",
+                ));
+            }
+
+            if train.on_network() {
+                track(train.track)?;
+                // How far along the track it is gets checked with the invariants
+                if !train.distance.is_finite() {
+                    return Err(LoadError::Corrupt("a train isn't anywhere on its track"));
+                }
+            }
+            (train.trailing.iter())
+                .chain(&train.route)
+                .chain(&train.path)
+                .try_for_each(|id| track(*id))?;
+
+            match train.destination {
+                Some(Destination::Junction(id)) => self.junction(id).map(|_| ())?,
+                Some(Destination::Track(id)) => track(id)?,
+                Some(Destination::Station(id)) => station(id)?,
+                None => {}
+            }
+            if let Some((id, _)) = train.bound_for {
+                depot(id)?;
+            }
+            if let Location::Depot(id) = train.location {
+                depot(id)?;
+            }
+            if let Some((id, _)) = train.dwelling {
+                station(id)?;
+            }
+            train.cargo.iter().try_for_each(cargo)?;
+
+            self.check_orders(&train.orders)?;
+            if train.order >= train.orders.len().max(1) {
+                return Err(LoadError::Corrupt("a train is past the end of its orders"));
+            }
+            if let Some(schedule) = &train.schedule {
+                schedule.timetable.check(&train.orders)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::Vec2;
+
+    use crate::{orders::Order, track::generate_network, track_shape::TrackShape};
+
+    /// The generated network with every train going round every station for
+    /// a while, so there's cargo, reserved paths and trains between tracks
+    fn running_network() -> Network {
+        let mut network = generate_network(3);
+        let orders: Vec<_> = network
+            .station_ids()
+            .flat_map(|station| [Order::GoTo(station), Order::Unload, Order::Load])
+            .collect();
+        for train in network.train_ids().collect::<Vec<_>>() {
+            network.set_orders(train, orders.clone()).unwrap();
+        }
+
+        for _ in 0..60 * 90 {
+            network.update(1.0 / 60.0);
+        }
+        network
+    }
+
+    /// Saves the network after breaking it, and loads it again
+    fn load_broken(break_network: impl FnOnce(&mut Network)) -> LoadError {
+        let mut network = running_network();
+        break_network(&mut network);
+
+        Network::load(&network.save())
+            .err()
+            .expect("the broken network loaded")
+    }
+
+    #[test]
+    fn loaded_networks_carry_on_the_same() {
+        let mut network = running_network();
+        let mut loaded = Network::load(&network.save()).unwrap();
+        assert_eq!(loaded.save(), network.save());
+
+        for _ in 0..60 * 60 {
+            network.update(1.0 / 60.0);
+            loaded.update(1.0 / 60.0);
+        }
+        assert_eq!(loaded.save(), network.save());
+    }
+
+    #[test]
+    fn malformed_saves_are_rejected() {
+        assert!(matches!(
+            Network::load("nonsense"),
+            Err(LoadError::Format(_))
+        ));
+
+        let newer = running_network().save().replacen(
+            &format!("version: {SAVE_VERSION}"),
+            "version: 99",
+            1,
+        );
+        assert!(matches!(
+            Network::load(&newer),
+            Err(LoadError::UnsupportedVersion(99))
+        ));
+    }
+
+    #[test]
+    fn impossible_trains_are_rejected() {
+        assert!(matches!(
+            load_broken(|network| network.trains[0].cars.clear()),
+            LoadError::Corrupt(_)
+        ));
+        assert!(matches!(
+            load_broken(|network| {
+                let train = &mut network.trains[0];
+                train.cargo = vec![
+                    Crate {
+                        origin: StationID(0),
+                        destination: StationID(1),
+                        created: 0.0,
+                    };
+                    train.capacity() + 1
+                ];
+            }),
+            LoadError::Corrupt(_)
+        ));
+        assert!(matches!(
+            load_broken(|network| network.trains[0].physics.braking = 0.0),
+            LoadError::Invalid(NetworkError::InvalidPhysics(_))
+        ));
+        assert!(matches!(
+            load_broken(|network| network.trains[0].distance = f32::NAN),
+            LoadError::Corrupt(_)
+        ));
+        assert!(matches!(
+            load_broken(|network| network.trains[0].distance = -5.0),
+            LoadError::Inconsistent(Violation::OffTrack { .. })
+        ));
+    }
+
+    #[test]
+    fn impossible_tracks_and_junctions_are_rejected() {
+        assert!(matches!(
+            load_broken(|network| network.tracks[0].length = 0.0),
+            LoadError::Corrupt(_)
+        ));
+        assert!(matches!(
+            load_broken(|network| network.junctions[0].position.x = f32::INFINITY),
+            LoadError::Corrupt(_)
+        ));
+        assert!(matches!(
+            load_broken(|network| network.tracks[0].length += 1.0),
+            LoadError::Corrupt(_)
+        ));
+        assert!(matches!(
+            load_broken(|network| network.tracks[0].speed_limit = Some(0.1)),
+            LoadError::Corrupt(_)
+        ));
+    }
+
+    #[test]
+    fn shapes_must_join_their_junctions() {
+        let line = |network: &Network, source: Vec2, direction: Vec2| TrackShape::Line {
+            source,
+            direction,
+            length: network.tracks[0].length,
+        };
+
+        assert!(matches!(
+            load_broken(|network| {
+                network.tracks[0].shape = line(network, Vec2::NAN, Vec2::X);
+            }),
+            LoadError::Corrupt(_)
+        ));
+        assert!(matches!(
+            load_broken(|network| {
+                let source = network.junctions[network.tracks[0].source.0].position;
+                let destination = network.junctions[network.tracks[0].destiation.0].position;
+                let away = (source - destination).normalize();
+                network.tracks[0].shape = line(network, source, away);
+            }),
+            LoadError::Corrupt(_)
+        ));
+    }
+
+    #[test]
+    fn impossible_network_settings_are_rejected() {
+        assert!(matches!(
+            load_broken(|network| network.balance = f32::NAN),
+            LoadError::Corrupt(_)
+        ));
+        assert!(matches!(
+            load_broken(|network| network.time = f32::INFINITY),
+            LoadError::Corrupt(_)
+        ));
+        assert!(matches!(
+            load_broken(|network| network.turning_radius = 0.5),
+            LoadError::Invalid(NetworkError::RadiusTooSmall(_))
+        ));
+        assert!(matches!(
+            load_broken(|network| network.stations[0].dwell_time = -1.0),
+            LoadError::Invalid(NetworkError::InvalidDwellTime(_))
+        ));
+        assert!(matches!(
+            load_broken(|network| network.stations[0].production.rate = f32::INFINITY),
+            LoadError::Invalid(NetworkError::InvalidProduction(_))
+        ));
+    }
+}
@@ -0,0 +1,387 @@
+use std::collections::VecDeque;
+
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+
+use crate::track::{JunctionId, Network, NetworkError, TrackID, TrainId};
+
+const SIGNAL_OFFSET: f32 = 1.2;
+/// Stops path searches running forever around loops without signals
+const MAX_PATH_TRACKS: usize = 64;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignalId(pub(crate) usize);
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct BlockId(pub(crate) usize);
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SignalKind {
+    /// Clear when nothing else is in the block behind it
+    Block,
+    /// Clear when the tracks the train will take up to the next signal are
+    /// free, so trains on paths that don't cross can be in the same block
+    Path,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Signal {
+    pub(crate) junction: JunctionId,
+    pub(crate) kind: SignalKind,
+}
+
+/// Tracks between signals. Only one train is allowed into a block at a time.
+pub struct Block {
+    pub(crate) tracks: Vec<TrackID>,
+}
+
+fn find_root(parents: &mut [usize], mut index: usize) -> usize {
+    while parents[index] != index {
+        parents[index] = parents[parents[index]];
+        index = parents[index];
+    }
+    index
+}
+
+impl Network {
+    /// Places a signal at the junction, splitting the blocks around it. Trains
+    /// wait at the signal until the block of the track they're about to enter
+    /// is free.
+    pub fn place_signal(&mut self, junction_id: JunctionId) -> Result<SignalId, NetworkError> {
+        self.place_signal_of_kind(junction_id, SignalKind::Block)
+    }
+
+    /// Places a signal at the junction that only waits for the tracks the
+    /// train will take up to the next signal, reserving them once clear
+    pub fn place_path_signal(&mut self, junction_id: JunctionId) -> Result<SignalId, NetworkError> {
+        self.place_signal_of_kind(junction_id, SignalKind::Path)
+    }
+
+    /// Replaces the kind of any signal already at the junction
+    fn place_signal_of_kind(
+        &mut self,
+        junction_id: JunctionId,
+        kind: SignalKind,
+    ) -> Result<SignalId, NetworkError> {
+        if let Some(signal) = self.junction(junction_id)?.signal {
+            self.signals[signal.0].kind = kind;
+            return Ok(signal);
+        }
+
+        let signal_id = SignalId(self.signals.len());
+        self.signals.push(Signal {
+            junction: junction_id,
+            kind,
+        });
+        self.junctions[junction_id.0].signal = Some(signal_id);
+
+        self.rebuild_blocks();
+
+        Ok(signal_id)
+    }
+
+    /// Groups tracks connected through junctions without signals into blocks
+    pub(crate) fn rebuild_blocks(&mut self) {
+        let mut parents: Vec<usize> = (0..self.tracks.len()).collect();
+
+        for junction in &self.junctions {
+            if junction.signal.is_some() {
+                continue;
+            }
+
+            let mut tracks = (&junction.enterances)
+                .into_iter()
+                .chain(&junction.exits)
+                .map(|track| track.0);
+
+            if let Some(first) = tracks.next() {
+                for track in tracks {
+                    let (a, b) = (
+                        find_root(&mut parents, first),
+                        find_root(&mut parents, track),
+                    );
+                    parents[a] = b;
+                }
+            }
+        }
+
+        self.blocks.clear();
+        let mut root_blocks = vec![None; self.tracks.len()];
+        for track in 0..self.tracks.len() {
+            let root = find_root(&mut parents, track);
+
+            let block = *root_blocks[root].get_or_insert_with(|| {
+                self.blocks.push(Block { tracks: vec![] });
+                BlockId(self.blocks.len() - 1)
+            });
+
+            self.blocks[block.0].tracks.push(TrackID(track));
+            self.tracks[track].block = block;
+        }
+    }
+
+    /// Whether a train other than `except` is on or has reserved the track
+    pub(crate) fn track_taken(&self, track: TrackID, except: Option<TrainId>) -> bool {
+        let track = &self.tracks[track.0];
+
+        track.reserved_by.is_some_and(|train| Some(train) != except)
+            || track.trains.iter().any(|train| Some(*train) != except)
+    }
+
+    /// Whether any train other than `except` is in the block, has reserved
+    /// part of it or is on a track conflicting with it
+    pub(crate) fn block_occupied(&self, block: BlockId, except: Option<TrainId>) -> bool {
+        self.blocks[block.0]
+            .tracks
+            .iter()
+            .flat_map(|track| std::iter::once(track).chain(&self.tracks[track.0].conflicts))
+            .any(|track| self.track_taken(*track, except))
+    }
+
+    /// Tracks from `next` up to the next signal in the direction of travel,
+    /// following `route` where it leaves a junction and the switches everywhere
+    /// else
+    pub(crate) fn signal_path(
+        &self,
+        next: TrackID,
+        backwards: bool,
+        route: &VecDeque<TrackID>,
+    ) -> Vec<TrackID> {
+        // The route may still start with the tracks up to the signal
+        let taken = route
+            .iter()
+            .position(|track| *track == next)
+            .map_or(0, |index| index + 1);
+        let mut route = route.iter().copied().skip(taken).peekable();
+
+        let mut path = vec![next];
+        while path.len() < MAX_PATH_TRACKS {
+            let junction = &self.junctions[self.tracks[path[path.len() - 1].0].end(backwards).0];
+            let leaving = junction.leaving(backwards);
+            if junction.signal.is_some() || leaving.is_empty() {
+                break;
+            }
+
+            let track = route
+                .next_if(|track| leaving.into_iter().any(|next| next == track))
+                .unwrap_or(leaving[junction.switch_for(backwards)]);
+            path.push(track);
+        }
+
+        path
+    }
+
+    /// Whether the path from the signal at `junction` is free for `train`. Every
+    /// junction the path passes through has to be free as a whole, since paths
+    /// through the same junction cross there even if they share no track, and
+    /// so does every track conflicting with the path.
+    fn path_free(
+        &self,
+        junction: JunctionId,
+        path: &[TrackID],
+        backwards: bool,
+        train: Option<TrainId>,
+    ) -> bool {
+        let signal_junction = &self.junctions[junction.0];
+        let through = path[..path.len() - 1]
+            .iter()
+            .map(|track| &self.junctions[self.tracks[track.0].end(backwards).0]);
+        let conflicts = path
+            .iter()
+            .flat_map(|track| &self.tracks[track.0].conflicts);
+
+        !signal_junction
+            .leaving(backwards)
+            .into_iter()
+            .chain(
+                through
+                    .flat_map(|junction| (&junction.enterances).into_iter().chain(&junction.exits)),
+            )
+            .chain(conflicts)
+            .any(|track| self.track_taken(*track, train))
+    }
+
+    /// Reserves the path for the train and sets the switches along it, after
+    /// any path it has already reserved. The train follows the path even if the
+    /// route or switches change, and frees each track again as it leaves it.
+    fn reserve_path(&mut self, train: TrainId, path: Vec<TrackID>) {
+        let backwards = self.trains[train.0].backwards;
+
+        for track in &path {
+            let track = &mut self.tracks[track.0];
+            track.reserved_by = Some(train);
+
+            // The junction the train enters the track from
+            self.junctions[track.end(!backwards).0].set_switch_to(track.id);
+        }
+
+        self.trains[train.0].path.extend(path);
+    }
+
+    /// Whether the train may pass the junction at the end of its track onto
+    /// `next`, reserving its path up to the next signal if so
+    pub(crate) fn try_pass_signal(
+        &mut self,
+        train: TrainId,
+        junction: JunctionId,
+        next: TrackID,
+    ) -> bool {
+        let Some(signal) = self.junctions[junction.0].signal else {
+            return true;
+        };
+
+        let backwards = self.trains[train.0].backwards;
+        let path = self.signal_path(next, backwards, &self.trains[train.0].route);
+
+        let clear = match self.signals[signal.0].kind {
+            SignalKind::Block => !self.block_occupied(self.tracks[next.0].block, Some(train)),
+            SignalKind::Path => self.path_free(junction, &path, backwards, Some(train)),
+        };
+
+        if clear {
+            self.reserve_path(train, path);
+        }
+
+        clear
+    }
+
+    /// Reserves the tracks the train is on and the ones from its front up to
+    /// the next signal if they're free. Trains that didn't get where they are
+    /// by passing a signal have to do this before they can move.
+    pub(crate) fn try_claim_path(&mut self, train_id: TrainId) -> bool {
+        let train = &self.trains[train_id.0];
+        let backwards = train.backwards;
+        let path = self.signal_path(train.track, backwards, &train.route);
+        let behind = self.tracks[train.track.0].end(!backwards);
+
+        if !self.path_free(behind, &path, backwards, Some(train_id))
+            || train
+                .trailing
+                .iter()
+                .any(|track| self.track_taken(*track, Some(train_id)))
+        {
+            return false;
+        }
+
+        for track in std::iter::once(&path[0]).chain(&self.trains[train_id.0].trailing) {
+            self.tracks[track.0].reserved_by = Some(train_id);
+        }
+        self.reserve_path(train_id, path[1..].to_vec());
+        self.trains[train_id.0].needs_path = false;
+
+        true
+    }
+
+    /// Whether the switch for one side of the junction is part of a reserved
+    /// path and can't be thrown
+    pub(crate) fn switch_locked(&self, junction: JunctionId, backwards: bool) -> bool {
+        self.junctions[junction.0]
+            .leaving(backwards)
+            .into_iter()
+            .any(|track| self.tracks[track.0].reserved_by.is_some())
+    }
+
+    /// Every signal with whether it shows clear for the track its junction is
+    /// set to, for trains going forwards
+    pub fn signal_states(&self) -> impl Iterator<Item = (Vec2, bool)> + '_ {
+        self.signals.iter().map(|signal| {
+            let junction = &self.junctions[signal.junction.0];
+            let offset = junction.direction.unwrap_or(Vec2::X).perp() * -SIGNAL_OFFSET;
+
+            let clear = !junction.exits.is_empty() && {
+                let next = junction.exits[junction.switch];
+
+                match signal.kind {
+                    SignalKind::Block => !self.block_occupied(self.tracks[next.0].block, None),
+                    SignalKind::Path => self.path_free(
+                        junction.id,
+                        &self.signal_path(next, false, &VecDeque::new()),
+                        false,
+                        None,
+                    ),
+                }
+            };
+
+            (junction.position + offset, clear)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orders::Order;
+    use crate::track::{generate_network, Event};
+
+    /// Junctions every 10 units along the x axis with a track between each pair
+    fn line(junctions: usize) -> (Network, Vec<JunctionId>, Vec<TrackID>) {
+        let mut network = Network::new(0);
+        network.balance = f32::INFINITY;
+
+        let junctions: Vec<_> = (0..junctions)
+            .map(|i| network.add_junction(Vec2::new(i as f32 * 10.0, 0.0)))
+            .collect();
+        let tracks = junctions
+            .windows(2)
+            .map(|pair| network.try_connect_track(pair[0], pair[1]).unwrap())
+            .collect();
+
+        (network, junctions, tracks)
+    }
+
+    #[test]
+    fn block_signal_holds_trains_out_of_occupied_blocks() {
+        for (ahead, clear) in [(2, false), (3, true)] {
+            let (mut network, junctions, tracks) = line(5);
+            network.place_signal(junctions[2]).unwrap();
+            network.place_signal(junctions[3]).unwrap();
+            let waiting = network.try_add_train(tracks[1]).unwrap();
+            network.try_add_train(tracks[ahead]).unwrap();
+
+            assert_eq!(
+                network.try_pass_signal(waiting, junctions[2], tracks[2]),
+                clear
+            );
+            assert_eq!(
+                network.tracks[tracks[2].0].reserved_by == Some(waiting),
+                clear
+            );
+        }
+    }
+
+    #[test]
+    fn path_signal_reserves_up_to_the_next_signal() {
+        let (mut network, junctions, tracks) = line(5);
+        network.place_path_signal(junctions[1]).unwrap();
+        network.place_path_signal(junctions[3]).unwrap();
+        let first = network.try_add_train(tracks[0]).unwrap();
+
+        assert!(network.try_pass_signal(first, junctions[1], tracks[1]));
+        for track in &tracks[1..3] {
+            assert_eq!(network.tracks[track.0].reserved_by, Some(first));
+        }
+        assert_eq!(network.tracks[tracks[3].0].reserved_by, None);
+        assert!(network.switch_locked(junctions[1], false));
+
+        // Anything else wanting the same path has to wait for it
+        assert!(!network.path_free(junctions[1], &tracks[1..3], false, None));
+    }
+
+    #[test]
+    fn signals_keep_the_generated_network_free_of_crashes() {
+        let mut network = generate_network(0);
+        let orders: Vec<_> = network.station_ids().map(Order::GoTo).collect();
+        for train in network.train_ids().collect::<Vec<_>>() {
+            network.set_orders(train, orders.clone()).unwrap();
+        }
+
+        for _ in 0..60 * 60 {
+            network.update(1.0 / 60.0);
+
+            assert!(!network
+                .drain_events()
+                .any(|event| matches!(event, Event::Crash { .. })));
+            assert_eq!(network.invariant_violations(), vec![]);
+        }
+    }
+}
@@ -1,25 +1,35 @@
-use std::{
-    collections::VecDeque,
-    f32::consts::{FRAC_PI_2, PI, TAU},
-    os::unix::net,
-};
+use std::{collections::VecDeque, fmt};
 
 use glam::Vec2;
-use rand::Rng;
-
-use crate::{minivec::Minivec, track_shape::TrackShape};
+use rand::SeedableRng;
+use rand_pcg::Pcg64;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cargo::{Crate, Production},
+    consist::{consist, Car},
+    depot::{Depot, DepotID, DepotVisit, Location},
+    dubins,
+    economy::{consist_price, track_cost, STARTING_BALANCE},
+    minivec::Minivec,
+    orders::{Order, Schedule},
+    physics::{curve_speed_limit, TrainPhysics},
+    routing,
+    signal::{Block, BlockId, Signal, SignalId},
+    track_shape::TrackShape,
+};
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
-pub struct StationID(usize);
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StationID(pub(crate) usize);
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
-pub struct TrackID(usize);
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub struct TrackID(pub(crate) usize);
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
-pub struct JunctionId(usize);
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JunctionId(pub(crate) usize);
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
-pub struct TrainId(usize);
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct TrainId(pub(crate) usize);
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct StationId;
@@ -27,24 +37,230 @@ pub struct StationId;
 const MAX_RADIUS: f32 = 4.0;
 const IDEAL_SEGMENT_LENGTH: f32 = 3.0;
 const MIN_RADIUS: f32 = 2.0;
+const SWITCH_INDICATOR_LENGTH: f32 = 1.5;
+pub const TRAIN_WIDTH: f32 = 1.2;
+/// How far before a junction the front of a train waiting there stops
+const STOP_DISTANCE: f32 = 0.2;
+/// How long trains wait at a station, in seconds, unless it's set otherwise
+const DEFAULT_DWELL_TIME: f32 = 10.0;
+/// Stops trains looking ahead forever around loops without stops
+const MAX_LOOKAHEAD_TRACKS: usize = 64;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum NetworkError {
+    UnknownJunction(JunctionId),
+    UnknownTrack(TrackID),
+    /// The junction already has as many tracks as it can hold on that side
+    JunctionFull(JunctionId),
+    NoFeasibleCurve {
+        source: JunctionId,
+        destination: JunctionId,
+    },
+    RadiusTooSmall(f32),
+    NotASwitch(JunctionId),
+    UnknownTrain(TrainId),
+    UnknownStation(StationID),
+    NoRoute {
+        from: TrackID,
+        destination: Destination,
+    },
+    NotAtJunction {
+        junction: JunctionId,
+        track: TrackID,
+    },
+    /// A train has reserved a path over the switch
+    SwitchLocked(JunctionId),
+    /// There isn't enough track behind the track to put the whole train on
+    TrainDoesNotFit(TrackID),
+    /// Building or buying something costs more than the network has
+    InsufficientFunds {
+        cost: f32,
+        balance: f32,
+    },
+    /// A timetable needs a departure for every station the orders go to
+    TimetableMismatch {
+        stops: usize,
+        departures: usize,
+    },
+    UnknownDepot(DepotID),
+    TrainInDepot(TrainId),
+    TrainNotInDepot(TrainId),
+    /// Every physics value has to be finite and positive
+    InvalidPhysics(TrainPhysics),
+    /// Stations can't make a negative or endless number of crates
+    InvalidProduction(f32),
+    /// Wagons have to be a finite, positive length
+    InvalidWagonLength(f32),
+    /// Timetables have to repeat after a finite, positive time
+    InvalidPeriod(f32),
+    /// Departures have to be at a time into the timetable's period
+    DepartureOutsidePeriod {
+        departure: f32,
+        period: f32,
+    },
+    /// Stations need a finite position and direction and a finite, positive
+    /// length
+    InvalidStation {
+        position: Vec2,
+        length: f32,
+        angle: f32,
+    },
+    /// Trains can't wait at stations for a negative or endless time
+    InvalidDwellTime(f32),
+}
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+impl fmt::Display for NetworkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetworkError::UnknownJunction(id) => write!(f, "unknown junction {id:?}"),
+            NetworkError::UnknownTrack(id) => write!(f, "unknown track {id:?}"),
+            NetworkError::JunctionFull(id) => write!(f, "junction {id:?} is full"),
+            NetworkError::NoFeasibleCurve {
+                source,
+                destination,
+            } => write!(f, "no feasible curve from {source:?} to {destination:?}"),
+            NetworkError::RadiusTooSmall(radius) => write!(
+                f,
+                "radius {radius} is below the minimum radius of {MIN_RADIUS}"
+            ),
+            NetworkError::NotASwitch(id) => write!(f, "junction {id:?} is not a switch"),
+            NetworkError::UnknownTrain(id) => write!(f, "unknown train {id:?}"),
+            NetworkError::UnknownStation(id) => write!(f, "unknown station {id:?}"),
+            NetworkError::NoRoute { from, destination } => {
+                write!(f, "no route from {from:?} to {destination:?}")
+            }
+            NetworkError::NotAtJunction { junction, track } => {
+                write!(
+                    f,
+                    "track {track:?} does not enter or leave junction {junction:?}"
+                )
+            }
+            NetworkError::SwitchLocked(id) => write!(f, "switch {id:?} is locked"),
+            NetworkError::TrainDoesNotFit(id) => {
+                write!(f, "not enough track behind {id:?} for the train")
+            }
+            NetworkError::InsufficientFunds { cost, balance } => {
+                write!(f, "costs {cost:.0} but the balance is only {balance:.0}")
+            }
+            NetworkError::TimetableMismatch { stops, departures } => {
+                write!(f, "{departures} departures for {stops} stops")
+            }
+            NetworkError::UnknownDepot(id) => write!(f, "unknown depot {id:?}"),
+            NetworkError::TrainInDepot(id) => write!(f, "train {id:?} is in a depot"),
+            NetworkError::TrainNotInDepot(id) => write!(f, "train {id:?} is not in a depot"),
+            NetworkError::InvalidPhysics(physics) => write!(f, "invalid train physics {physics:?}"),
+            NetworkError::InvalidProduction(rate) => {
+                write!(f, "stations can't make {rate} crates a second")
+            }
+            NetworkError::InvalidWagonLength(length) => {
+                write!(f, "wagons can't be {length} long")
+            }
+            NetworkError::InvalidPeriod(period) => {
+                write!(f, "timetables can't repeat every {period} seconds")
+            }
+            NetworkError::DepartureOutsidePeriod { departure, period } => {
+                write!(
+                    f,
+                    "departure at {departure} isn't within the period of {period}"
+                )
+            }
+            NetworkError::InvalidStation {
+                position,
+                length,
+                angle,
+            } => write!(
+                f,
+                "no station {length} long can be at {position} facing {angle}"
+            ),
+            NetworkError::InvalidDwellTime(dwell_time) => {
+                write!(f, "trains can't wait {dwell_time} seconds at stations")
+            }
+        }
+    }
+}
+
+impl std::error::Error for NetworkError {}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Junction {
-    #[allow(unused)]
-    id: JunctionId,
-    position: Vec2,
-    enterances: Minivec<2, TrackID>,
-    exits: Minivec<2, TrackID>,
-    direction: Option<Vec2>,
+    pub(crate) id: JunctionId,
+    pub(crate) position: Vec2,
+    #[serde(rename = "entrances")]
+    pub(crate) enterances: Minivec<2, TrackID>,
+    pub(crate) exits: Minivec<2, TrackID>,
+    pub(crate) direction: Option<Vec2>,
+    /// Index into `exits` of the track trains will take
+    pub(crate) switch: usize,
+    /// Index into `enterances` of the track trains going backwards will take
+    pub(crate) enterance_switch: usize,
+    pub(crate) signal: Option<SignalId>,
+    /// How many times trains have gone through
+    pub(crate) visits: usize,
+}
+
+impl Junction {
+    /// The tracks a train can leave the junction on, going forwards or
+    /// backwards
+    pub(crate) fn leaving(&self, backwards: bool) -> &Minivec<2, TrackID> {
+        if backwards {
+            &self.enterances
+        } else {
+            &self.exits
+        }
+    }
+
+    /// Index into `leaving` of the track trains will take
+    pub(crate) fn switch_for(&self, backwards: bool) -> usize {
+        if backwards {
+            self.enterance_switch
+        } else {
+            self.switch
+        }
+    }
+
+    /// Sets the switch on whichever side the track is on, if it's on either
+    pub(crate) fn set_switch_to(&mut self, track: TrackID) {
+        if let Some(index) = (&self.exits).into_iter().position(|exit| *exit == track) {
+            self.switch = index;
+        } else if let Some(index) = (&self.enterances)
+            .into_iter()
+            .position(|enterance| *enterance == track)
+        {
+            self.enterance_switch = index;
+        }
+    }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct Track {
-    id: TrackID,
-    source: JunctionId,
-    destiation: JunctionId,
-    trains: VecDeque<TrainId>,
-    length: f32,
-    shape: TrackShape,
+    pub(crate) id: TrackID,
+    pub(crate) source: JunctionId,
+    #[serde(rename = "destination")]
+    pub(crate) destiation: JunctionId,
+    pub(crate) trains: VecDeque<TrainId>,
+    pub(crate) length: f32,
+    pub(crate) shape: TrackShape,
+    /// How fast trains may go around the track's curve, `None` if it is
+    /// straight
+    pub(crate) speed_limit: Option<f32>,
+    /// Worked out again from the signals when the network is loaded
+    #[serde(skip)]
+    pub(crate) block: BlockId,
+    pub(crate) reserved_by: Option<TrainId>,
+    /// Tracks that trains on this one could touch, see `find_conflicts`
+    pub(crate) conflicts: Vec<TrackID>,
+}
+
+impl Track {
+    /// The junction trains leave the track through, going forwards or
+    /// backwards
+    pub(crate) fn end(&self, backwards: bool) -> JunctionId {
+        if backwards {
+            self.source
+        } else {
+            self.destiation
+        }
+    }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -52,30 +268,220 @@ pub struct TrackInfo {
     pub source: Vec2,
     pub destination: Vec2,
     pub shape: TrackShape,
+    pub speed_limit: Option<f32>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct StationInfo {
+    pub position: Vec2,
+    pub angle: f32,
+    pub length: f32,
+    /// How many crates are waiting to be picked up
+    pub waiting: usize,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TrainInfo {
+    pub id: TrainId,
+    pub position: Vec2,
+    pub angle: f32,
+    pub speed: f32,
+    pub cargo: usize,
+    pub capacity: usize,
+    /// Metres run since the train was added
+    pub travelled: f32,
+    pub crashed: bool,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Event {
+    /// Two trains ran into each other and have stopped where they are
+    Crash {
+        trains: [TrainId; 2],
+        position: Vec2,
+    },
+    /// A train came to a halt at a junction with no track to leave on
+    ReachedDeadEnd {
+        train: TrainId,
+        junction: JunctionId,
+    },
+    ArrivedAtStation {
+        train: TrainId,
+        station: StationID,
+    },
+    DepartedStation {
+        train: TrainId,
+        station: StationID,
+        /// How long after its timetabled departure the train left, for trains
+        /// keeping one
+        lateness: Option<f32>,
+    },
+    /// A train unloaded a crate at the station it was for
+    CrateDelivered {
+        train: TrainId,
+        cargo: Crate,
+        revenue: f32,
+    },
+    EnteredDepot {
+        train: TrainId,
+        depot: DepotID,
+    },
+    LeftDepot {
+        train: TrainId,
+        depot: DepotID,
+    },
 }
 
+/// Where a train might have to stop before a junction
+#[derive(Copy, Clone)]
+enum Stop {
+    Signal { junction: JunctionId, next: TrackID },
+    Buffer(JunctionId),
+    Station(StationID),
+    Depot(DepotID),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Destination {
+    Junction(JunctionId),
+    Track(TrackID),
+    Station(StationID),
+}
+
+#[derive(Serialize, Deserialize)]
 pub struct Train {
-    id: TrainId,
-    track: TrackID,
-    distance: f32,
+    pub(crate) id: TrainId,
+    /// The track the front of the train is on
+    pub(crate) track: TrackID,
+    /// Where the front of the train is, always measured from the track's
+    /// source whichever way the train goes
+    pub(crate) distance: f32,
+    /// Tracks behind `track` the rest of the train is still on, nearest first
+    pub(crate) trailing: VecDeque<TrackID>,
+    /// First car first, in the direction the train is going
+    pub(crate) cars: Vec<Car>,
+    /// Whether the train runs from the track's destination to its source
+    pub(crate) backwards: bool,
+    pub(crate) speed: f32,
+    pub(crate) physics: TrainPhysics,
+    /// Whether the train turns around once it has braked to a halt
+    pub(crate) reversing: bool,
+    /// Whether the train has to reserve the tracks up to the next signal
+    /// before it can move, as it didn't pass a signal to get where it is
+    pub(crate) needs_path: bool,
+    pub(crate) location: Location,
+    pub(crate) destination: Option<Destination>,
+    /// The depot the train is heading into at `destination`, and what for
+    pub(crate) bound_for: Option<(DepotID, DepotVisit)>,
+    /// Tracks still to take to reach `destination`, in order
+    pub(crate) route: VecDeque<TrackID>,
+    /// Reserved tracks the train hasn't entered yet
+    pub(crate) path: VecDeque<TrackID>,
+    /// The station the train stopped at and how much longer it waits there
+    pub(crate) dwelling: Option<(StationID, f32)>,
+    pub(crate) cargo: Vec<Crate>,
+    /// What the train does, in turn, over and over
+    pub(crate) orders: Vec<Order>,
+    /// The order the train is carrying out
+    pub(crate) order: usize,
+    pub(crate) schedule: Option<Schedule>,
+    /// When the timetable has the train leave the station it's at
+    pub(crate) departure_due: Option<f32>,
+    /// Metres run since the train was last serviced
+    pub(crate) wear: f32,
+    pub(crate) travelled: f32,
+    pub(crate) crashed: bool,
+}
+
+impl Train {
+    /// A train that isn't anywhere on the network yet
+    pub(crate) fn new(id: TrainId, cars: Vec<Car>) -> Train {
+        Train {
+            id,
+            track: TrackID::default(),
+            distance: 0.0,
+            trailing: VecDeque::new(),
+            cars,
+            backwards: false,
+            speed: 0.0,
+            physics: TrainPhysics::default(),
+            reversing: false,
+            needs_path: true,
+            location: Location::Network,
+            destination: None,
+            bound_for: None,
+            route: VecDeque::new(),
+            path: VecDeque::new(),
+            dwelling: None,
+            cargo: vec![],
+            orders: vec![],
+            order: 0,
+            schedule: None,
+            departure_due: None,
+            wear: 0.0,
+            travelled: 0.0,
+            crashed: false,
+        }
+    }
 }
 
-struct Station {
-    position: Vec2,
-    length: f32,
-    track: TrackID,
-    angle: f32,
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Station {
+    pub(crate) position: Vec2,
+    pub(crate) length: f32,
+    /// The first of the station's tracks
+    pub(crate) track: TrackID,
+    pub(crate) end: JunctionId,
+    pub(crate) angle: f32,
+    pub(crate) dwell_time: f32,
+    pub(crate) production: Production,
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct Network {
-    tracks: Vec<Track>,
-    junctions: Vec<Junction>,
-    trains: Vec<Train>,
-    stations: Vec<Station>,
+    pub(crate) tracks: Vec<Track>,
+    pub(crate) junctions: Vec<Junction>,
+    pub(crate) trains: Vec<Train>,
+    pub(crate) stations: Vec<Station>,
+    pub(crate) turning_radius: f32,
+    pub(crate) signals: Vec<Signal>,
+    #[serde(skip)]
+    pub(crate) blocks: Vec<Block>,
+    /// Events not drained yet are lost when the network is saved
+    #[serde(skip)]
+    pub(crate) events: Vec<Event>,
+    /// Whether trains turn around by themselves at dead ends
+    pub(crate) auto_reverse: bool,
+    /// Seconds simulated so far
+    pub(crate) time: f32,
+    pub(crate) depots: Vec<Depot>,
+    /// Money left to build and buy with
+    pub(crate) balance: f32,
+    /// Where everything random in the simulation comes from, so the same seed
+    /// and the same calls always play out the same way
+    pub(crate) rng: Pcg64,
 }
 
 impl Network {
-    fn add_junction(&mut self, position: Vec2) -> JunctionId {
+    pub fn new(seed: u64) -> Network {
+        Network {
+            tracks: vec![],
+            trains: vec![],
+            junctions: vec![],
+            stations: vec![],
+            turning_radius: MAX_RADIUS,
+            signals: vec![],
+            blocks: vec![],
+            events: vec![],
+            auto_reverse: false,
+            time: 0.0,
+            depots: vec![],
+            balance: STARTING_BALANCE,
+            rng: Pcg64::seed_from_u64(seed),
+        }
+    }
+
+    pub fn add_junction(&mut self, position: Vec2) -> JunctionId {
         let junction_id = JunctionId(self.junctions.len());
 
         self.junctions.push(Junction {
@@ -84,14 +490,31 @@ impl Network {
             exits: Minivec::new(),
             enterances: Minivec::new(),
             direction: None,
+            switch: 0,
+            enterance_switch: 0,
+            signal: None,
+            visits: 0,
         });
 
-        return junction_id;
+        junction_id
     }
 
-    fn assert_correctness(&self, message: &'static str) {
-        return;
+    /// The junction closest to the position, `None` if there are none
+    pub fn nearest_junction(&self, position: Vec2) -> Option<JunctionId> {
+        self.junctions
+            .iter()
+            .min_by(|a, b| {
+                a.position
+                    .distance_squared(position)
+                    .total_cmp(&b.position.distance_squared(position))
+            })
+            .map(|junction| junction.id)
+    }
 
+    /// Panics if a junction is out of place or a track doesn't start and end
+    /// where its junctions are. Only debug builds check.
+    #[cfg(debug_assertions)]
+    fn assert_correctness(&self, message: &'static str) {
         for (id, junction) in self.junctions.iter().enumerate() {
             assert_eq!(JunctionId(id), junction.id)
         }
@@ -116,149 +539,336 @@ impl Network {
         }
     }
 
-    fn create_line(&mut self, source_id: JunctionId, destination_id: JunctionId) -> TrackID {
-        let source = self.junctions[source_id.0];
-        let destination = self.junctions[destination_id.0];
+    pub(crate) fn junction(&self, id: JunctionId) -> Result<&Junction, NetworkError> {
+        self.junctions
+            .get(id.0)
+            .ok_or(NetworkError::UnknownJunction(id))
+    }
 
-        let angle = (destination.position - source.position).normalize();
-        let track = self.add_track(
-            source_id,
-            destination_id,
-            TrackShape::Line {
-                source: self.junctions[source_id.0].position,
-                direction: angle,
-                length: (destination.position - source.position).length(),
-            },
-        );
-        self.junctions[source_id.0].direction.get_or_insert(angle);
-        self.junctions[destination_id.0]
-            .direction
-            .get_or_insert(angle);
+    pub(crate) fn station(&self, id: StationID) -> Result<&Station, NetworkError> {
+        self.stations
+            .get(id.0)
+            .ok_or(NetworkError::UnknownStation(id))
+    }
+
+    pub(crate) fn train(&self, id: TrainId) -> Result<&Train, NetworkError> {
+        self.trains
+            .get(id.0)
+            .filter(|train| train.location != Location::Removed)
+            .ok_or(NetworkError::UnknownTrain(id))
+    }
 
-        track
+    /// The train, if it's out on the network rather than in a depot
+    pub(crate) fn train_on_network(&self, id: TrainId) -> Result<&Train, NetworkError> {
+        let train = self.train(id)?;
+        if !train.on_network() {
+            return Err(NetworkError::TrainInDepot(id));
+        }
+
+        Ok(train)
+    }
+
+    fn plan_connection(&self, source: &Junction, destination: &Junction) -> Vec<TrackShape> {
+        match (source.direction, destination.direction) {
+            (None, None) => vec![TrackShape::Line {
+                source: source.position,
+                direction: (destination.position - source.position).normalize(),
+                length: (destination.position - source.position).length(),
+            }],
+            (Some(source_direction), None) => TrackShape::eased_from_source_direction_dest(
+                source.position,
+                source_direction,
+                destination.position,
+            ),
+            (None, Some(destination_direction)) => TrackShape::eased_from_source_direction_dest(
+                destination.position,
+                -destination_direction,
+                source.position,
+            )
+            .into_iter()
+            .rev()
+            .map(TrackShape::reverse)
+            .collect(),
+            (Some(source_direction), Some(destination_direction)) => dubins::shortest_eased_path(
+                source.position,
+                source_direction,
+                destination.position,
+                destination_direction,
+                self.turning_radius,
+            ),
+        }
     }
 
     fn connect_track(&mut self, source_id: JunctionId, destination_id: JunctionId) -> TrackID {
-        let source = self.junctions[source_id.0];
-        let destiation = self.junctions[destination_id.0];
-
-        #[derive(Debug)]
-        struct ArcInfo {
-            radius: f32,
-            final_vector: Vec2,
-            angle_distance: f32,
-            center: Vec2,
-            start_vector: Vec2,
-        }
-
-        match (source.direction, destiation.direction) {
-            (None, None) => self.create_line(source_id, destination_id),
-            (Some(source_direction), None) => {
-                let arc = TrackShape::from_source_direction_dest(
-                    source.position,
-                    source_direction,
-                    destiation.position,
-                );
-
-                self.junctions[destination_id.0].direction = Some(Vec2::from_angle(
-                    arc.get_transform_at_distance(arc.get_length()).1,
-                ));
-                self.add_track(source_id, destination_id, arc)
-            }
-            (None, Some(destination_direction)) => {
-                let arc = TrackShape::from_source_direction_dest(
-                    destiation.position,
-                    -destination_direction,
-                    source.position,
-                )
-                .reverse();
+        self.try_connect_track(source_id, destination_id).unwrap()
+    }
 
-                self.junctions[source_id.0].direction =
-                    Some(Vec2::from_angle(arc.get_transform_at_distance(0.0).1));
-                self.add_track(source_id, destination_id, arc)
-            }
-            (Some(a1), Some(a2)) => {
-                let (midpoint_1, midpoint_2) = ([-1., 1.])
-                    .into_iter()
-                    .flat_map(|a| [-1.0, 1.0].map(|b| (a, b)))
-                    .filter_map(|(sign_1, sign_2)| {
-                        let c1 = source.position + sign_1 * (a1.perp()) * MAX_RADIUS;
-                        let c2 = destiation.position + sign_2 * (a2.perp()) * MAX_RADIUS;
-
-                        let direction = (c2 - c1).normalize();
-
-                        let midpoint_1 = c1 - direction.perp() * MAX_RADIUS * sign_1;
-                        let midpoint_2 = c2 - direction.perp() * MAX_RADIUS * sign_2;
-
-                        let target_direction_vector = destiation.position - source.position;
-
-                        if (midpoint_1 - source.position).dot(target_direction_vector) > 0.0
-                            && (midpoint_2 - midpoint_1).dot(target_direction_vector) > 0.0
-                            && (destiation.position - midpoint_2).dot(target_direction_vector) > 0.0
-                        // && (midpoint_1 - source.position).dot(a1) >= 0.0
-                        // && (destiation.position - midpoint_2).dot(-a2) >= 0.0
-                        {
-                            Some((midpoint_1, midpoint_2))
-                        } else {
-                            None
-                        }
-                    })
-                    .next()
-                    .unwrap();
+    pub fn try_connect_track(
+        &mut self,
+        source_id: JunctionId,
+        destination_id: JunctionId,
+    ) -> Result<TrackID, NetworkError> {
+        let shapes =
+            self.plan_connection(self.junction(source_id)?, self.junction(destination_id)?);
 
-                let junction_1 = self.add_junction(midpoint_1);
-                let junction_2 = self.add_junction(midpoint_2);
+        self.try_add_track_path(source_id, destination_id, &shapes)
+    }
 
-                self.connect_track(source_id, junction_1);
-                self.assert_correctness("after first bend");
-                self.connect_track(junction_2, destination_id);
-                self.assert_correctness("after second bend");
+    /// Sets the tightest radius connections between directed junctions may
+    /// curve with, eased ends included
+    pub fn set_turning_radius(&mut self, radius: f32) -> Result<(), NetworkError> {
+        check_turning_radius(radius)?;
 
-                self.create_line(junction_1, junction_2)
-            }
-        }
+        self.turning_radius = radius;
+        Ok(())
+    }
+
+    pub fn try_connect_spline(
+        &mut self,
+        source_id: JunctionId,
+        destination_id: JunctionId,
+    ) -> Result<TrackID, NetworkError> {
+        let source = self.junction(source_id)?;
+        let destination = self.junction(destination_id)?;
+
+        let straight_direction = (destination.position - source.position).normalize();
+
+        let spline = TrackShape::from_source_direction_dest_direction(
+            source.position,
+            source.direction.unwrap_or(straight_direction),
+            destination.position,
+            destination.direction.unwrap_or(straight_direction),
+        );
+
+        self.try_add_track_path(source_id, destination_id, &[spline])
+    }
+
+    /// The junction trains enter the station from going forwards, to connect
+    /// track leading to the station to
+    pub fn get_start_junction(&self, station: StationID) -> Result<JunctionId, NetworkError> {
+        Ok(self.tracks[self.station(station)?.track.0].source)
     }
 
-    fn get_start_junction(&self, station: StationID) -> JunctionId {
-        self.tracks[self.stations[station.0].track.0].source
+    /// The junction trains leave the station from going forwards, to connect
+    /// track leading away from the station to
+    pub fn get_end_junction(&self, station: StationID) -> Result<JunctionId, NetworkError> {
+        Ok(self.station(station)?.end)
     }
 
-    fn get_end_junction(&self, station: StationID) -> JunctionId {
-        self.tracks[self.stations[station.0].track.0].destiation
+    /// The junction trains stopping at the station stop in front of, going
+    /// forwards or backwards
+    fn station_exit(&self, station: StationID, backwards: bool) -> JunctionId {
+        let station = &self.stations[station.0];
+        if backwards {
+            self.tracks[station.track.0].source
+        } else {
+            station.end
+        }
     }
 
     fn add_station(&mut self, position: Vec2, length: f32, angle: f32) -> StationID {
+        self.try_add_station(position, length, angle).unwrap()
+    }
+
+    /// Adds a straight station track of its own, to be connected to the rest
+    /// of the network through its start and end junctions
+    pub fn try_add_station(
+        &mut self,
+        position: Vec2,
+        length: f32,
+        angle: f32,
+    ) -> Result<StationID, NetworkError> {
+        if !(position.is_finite() && angle.is_finite() && length.is_finite() && length > 0.0) {
+            return Err(NetworkError::InvalidStation {
+                position,
+                length,
+                angle,
+            });
+        }
+
         let station_id = StationID(self.stations.len());
+        let direction = Vec2::from_angle(angle);
+        let shape = TrackShape::Line {
+            source: position,
+            direction,
+            length,
+        };
+        // Paid for before the junctions are added, so nothing is left over
+        // if it can't be
+        self.charge(track_cost(&[shape]))?;
 
         let start_junction = self.add_junction(position);
-        let end_junction = self.add_junction(position + Vec2::from_angle(angle) * length);
-
-        let segment = self.add_track(
-            start_junction,
-            end_junction,
-            TrackShape::Line {
-                source: position,
-                direction: Vec2::from_angle(angle),
-                length,
-            },
-        );
+        let end_junction = self.add_junction(position + direction * length);
+        let segment = self.add_track_path(start_junction, end_junction, &[shape])?;
 
         self.stations.push(Station {
             position,
             length,
             angle,
             track: segment,
+            end: end_junction,
+            dwell_time: DEFAULT_DWELL_TIME,
+            production: Production::default(),
         });
 
-        return station_id;
+        Ok(station_id)
+    }
+
+    /// Sets how long trains wait at the station, in seconds
+    pub fn set_dwell_time(
+        &mut self,
+        station: StationID,
+        dwell_time: f32,
+    ) -> Result<(), NetworkError> {
+        if !(dwell_time.is_finite() && dwell_time >= 0.0) {
+            return Err(NetworkError::InvalidDwellTime(dwell_time));
+        }
+
+        self.stations
+            .get_mut(station.0)
+            .ok_or(NetworkError::UnknownStation(station))?
+            .dwell_time = dwell_time;
+
+        Ok(())
+    }
+
+    pub fn nearest_station(&self, position: Vec2, max_distance: f32) -> Option<StationID> {
+        self.stations
+            .iter()
+            .enumerate()
+            .map(|(index, station)| {
+                let direction = Vec2::from_angle(station.angle);
+                let middle = station.position + direction * station.length * 0.5;
+                let distance = middle.distance(position) - station.length * 0.5;
+
+                (StationID(index), distance)
+            })
+            .filter(|(_, distance)| *distance <= max_distance)
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(id, _)| id)
+    }
+
+    pub fn stations(&self) -> impl Iterator<Item = StationInfo> + '_ {
+        self.stations.iter().map(|station| StationInfo {
+            position: station.position,
+            angle: station.angle,
+            length: station.length,
+            waiting: station.production.waiting.len(),
+        })
+    }
+
+    pub fn station_ids(&self) -> impl Iterator<Item = StationID> {
+        (0..self.stations.len()).map(StationID)
+    }
+
+    pub fn try_add_track(
+        &mut self,
+        source_id: JunctionId,
+        destination_id: JunctionId,
+        shape: TrackShape,
+    ) -> Result<TrackID, NetworkError> {
+        self.try_add_track_path(source_id, destination_id, &[shape])
+    }
+
+    /// Checks everything that could make adding the path fail, so nothing is
+    /// added at all if it can't be added completely
+    fn check_track_path(
+        &self,
+        source_id: JunctionId,
+        destination_id: JunctionId,
+        shapes: &[TrackShape],
+    ) -> Result<(), NetworkError> {
+        let source = self.junction(source_id)?;
+        let destination = self.junction(destination_id)?;
+
+        if source.exits.is_full() {
+            return Err(NetworkError::JunctionFull(source_id));
+        }
+        if destination.enterances.is_full() {
+            return Err(NetworkError::JunctionFull(destination_id));
+        }
+
+        let no_feasible_curve = NetworkError::NoFeasibleCurve {
+            source: source_id,
+            destination: destination_id,
+        };
+
+        if !path_fits(shapes, source.position, destination.position) {
+            return Err(no_feasible_curve);
+        }
+
+        if let Some(radius) = shapes
+            .iter()
+            .filter_map(TrackShape::min_radius)
+            .find(|radius| *radius < MIN_RADIUS)
+        {
+            return Err(NetworkError::RadiusTooSmall(radius));
+        }
+
+        Ok(())
+    }
+
+    fn try_add_track_path(
+        &mut self,
+        source_id: JunctionId,
+        destination_id: JunctionId,
+        shapes: &[TrackShape],
+    ) -> Result<TrackID, NetworkError> {
+        self.check_track_path(source_id, destination_id, shapes)?;
+        self.charge(track_cost(shapes))?;
+
+        self.add_track_path(source_id, destination_id, shapes)
+    }
+
+    /// Adds the path without checking it or paying for it
+    fn add_track_path(
+        &mut self,
+        source_id: JunctionId,
+        destination_id: JunctionId,
+        shapes: &[TrackShape],
+    ) -> Result<TrackID, NetworkError> {
+        let mut last_junction = source_id;
+        let mut first_track = None;
+
+        for (index, shape) in shapes.iter().enumerate() {
+            let junction = if index == shapes.len() - 1 {
+                destination_id
+            } else {
+                let (position, rotation) = shape.get_transform_at_distance(shape.get_length());
+
+                let junction = self.add_junction(position);
+                self.junctions[junction.0].direction = Some(Vec2::from_angle(rotation));
+
+                junction
+            };
+
+            let track = self.add_track_segments(last_junction, junction, *shape)?;
+            first_track.get_or_insert(track);
+            last_junction = junction;
+        }
+
+        let first = shapes[0];
+        let last = shapes[shapes.len() - 1];
+        self.junctions[source_id.0]
+            .direction
+            .get_or_insert(Vec2::from_angle(first.get_transform_at_distance(0.0).1));
+        self.junctions[destination_id.0]
+            .direction
+            .get_or_insert(Vec2::from_angle(
+                last.get_transform_at_distance(last.get_length()).1,
+            ));
+
+        self.rebuild_blocks();
+
+        Ok(first_track.unwrap())
     }
 
-    fn add_track(
+    fn add_track_segments(
         &mut self,
         source_id: JunctionId,
         destination_id: JunctionId,
         shape: TrackShape,
-    ) -> TrackID {
+    ) -> Result<TrackID, NetworkError> {
         let length = shape.get_length();
 
         let number_of_segments = ((length / IDEAL_SEGMENT_LENGTH).floor() as usize).max(1);
@@ -288,12 +898,12 @@ impl Network {
                     (seg as f32) * segment_length,
                     (seg as f32 + 1.0) * segment_length,
                 ),
-            );
+            )?;
             first_segment.get_or_insert(segment);
             last_segment = destination_id;
         }
 
-        first_segment.unwrap()
+        Ok(first_segment.unwrap())
     }
 
     fn add_track_segment(
@@ -301,9 +911,16 @@ impl Network {
         source_id: JunctionId,
         destination_id: JunctionId,
         shape: TrackShape,
-    ) -> TrackID {
+    ) -> Result<TrackID, NetworkError> {
         let track_id = TrackID(self.tracks.len());
 
+        if self.junction(source_id)?.exits.is_full() {
+            return Err(NetworkError::JunctionFull(source_id));
+        }
+        if self.junction(destination_id)?.enterances.is_full() {
+            return Err(NetworkError::JunctionFull(destination_id));
+        }
+
         self.junctions[source_id.0].exits.push(track_id).unwrap();
         self.junctions[destination_id.0]
             .enterances
@@ -316,74 +933,660 @@ impl Network {
             trains: VecDeque::new(),
             length: shape.get_length(),
             id: track_id,
+            speed_limit: curve_speed_limit(&shape),
             shape,
+            block: BlockId::default(),
+            reserved_by: None,
+            conflicts: vec![],
         };
 
         self.tracks.push(track);
 
-        return track_id;
+        for other in self.find_conflicts(track_id) {
+            self.tracks[other.0].conflicts.push(track_id);
+            self.tracks[track_id.0].conflicts.push(other);
+        }
+
+        Ok(track_id)
     }
 
-    fn add_train(&mut self, track: TrackID) -> TrainId {
+    fn add_consist(&mut self, track: TrackID, wagons: &[f32]) -> TrainId {
+        self.try_add_consist(track, wagons).unwrap()
+    }
+
+    pub fn try_add_train(&mut self, track: TrackID) -> Result<TrainId, NetworkError> {
+        self.try_add_consist(track, &[])
+    }
+
+    /// Adds a locomotive pulling wagons of the given lengths, with its front at
+    /// the end of the track and the rest of it on the tracks behind
+    pub fn try_add_consist(
+        &mut self,
+        track: TrackID,
+        wagons: &[f32],
+    ) -> Result<TrainId, NetworkError> {
+        if track.0 >= self.tracks.len() {
+            return Err(NetworkError::UnknownTrack(track));
+        }
+
         let train_id = TrainId(self.trains.len());
 
-        let train = Train {
-            track: track,
-            distance: 0.0,
-            id: train_id,
-        };
+        let mut train = Train::new(train_id, consist(wagons)?);
+        train.track = track;
+        train.distance = self.tracks[track.0].length;
+
+        let mut covered = self.tracks[track.0].length;
+        let mut junction = &self.junctions[self.tracks[track.0].source.0];
+        while covered < train.length() {
+            if junction.enterances.is_empty() {
+                return Err(NetworkError::TrainDoesNotFit(track));
+            }
+
+            let behind = &self.tracks[junction.enterances[junction.enterance_switch].0];
+            train.trailing.push_back(behind.id);
+            covered += behind.length;
+            junction = &self.junctions[behind.source.0];
+        }
+
+        self.charge(consist_price(&train.cars))?;
 
+        for track in std::iter::once(train.track).chain(train.trailing.iter().copied()) {
+            let track = &mut self.tracks[track.0];
+            track.trains.push_back(train_id);
+            track.reserved_by.get_or_insert(train_id);
+        }
         self.trains.push(train);
 
-        self.tracks[track.0].trains.push_back(train_id);
+        Ok(train_id)
+    }
+
+    /// Sets the switch for the side of the junction the track is on
+    pub fn set_switch(
+        &mut self,
+        junction_id: JunctionId,
+        track: TrackID,
+    ) -> Result<(), NetworkError> {
+        let junction = self.junction(junction_id)?;
+        let Some(backwards) = [false, true].into_iter().find(|backwards| {
+            junction
+                .leaving(*backwards)
+                .into_iter()
+                .any(|leaving| *leaving == track)
+        }) else {
+            return Err(NetworkError::NotAtJunction {
+                junction: junction_id,
+                track,
+            });
+        };
+        if self.switch_locked(junction_id, backwards) {
+            return Err(NetworkError::SwitchLocked(junction_id));
+        }
+
+        self.junctions[junction_id.0].set_switch_to(track);
+        Ok(())
+    }
+
+    /// Sets the switch to the next exit, or the next enterance if the junction
+    /// only branches backwards, returning the track it now points to
+    pub fn throw_switch(&mut self, junction_id: JunctionId) -> Result<TrackID, NetworkError> {
+        let junction = *self.junction(junction_id)?;
+        let Some(backwards) = [false, true]
+            .into_iter()
+            .find(|backwards| junction.leaving(*backwards).len() >= 2)
+        else {
+            return Err(NetworkError::NotASwitch(junction_id));
+        };
+        if self.switch_locked(junction_id, backwards) {
+            return Err(NetworkError::SwitchLocked(junction_id));
+        }
+
+        let tracks = junction.leaving(backwards);
+        let track = tracks[(junction.switch_for(backwards) + 1) % tracks.len()];
+        self.junctions[junction_id.0].set_switch_to(track);
 
-        return train_id;
+        Ok(track)
     }
 
-    pub fn train_positions<'a>(&'a self) -> impl Iterator<Item = (Vec2, f32)> + 'a {
-        self.trains.iter().map(|train| {
-            let track = &self.tracks[train.track.0];
+    pub fn nearest_switch(&self, position: Vec2, max_distance: f32) -> Option<JunctionId> {
+        self.junctions
+            .iter()
+            .filter(|junction| junction.exits.len() >= 2 || junction.enterances.len() >= 2)
+            .filter(|junction| junction.position.distance(position) <= max_distance)
+            .min_by(|a, b| {
+                a.position
+                    .distance_squared(position)
+                    .total_cmp(&b.position.distance_squared(position))
+            })
+            .map(|junction| junction.id)
+    }
 
-            track.shape.get_transform_at_distance(train.distance)
+    /// Every switch with the point a short distance along the track it is set
+    /// to, for both sides of junctions that branch both ways
+    pub fn switch_positions<'a>(&'a self) -> impl Iterator<Item = (Vec2, Vec2)> + 'a {
+        self.junctions.iter().flat_map(move |junction| {
+            [false, true]
+                .into_iter()
+                .filter(|backwards| junction.leaving(*backwards).len() >= 2)
+                .map(move |backwards| {
+                    let track =
+                        &self.tracks[junction.leaving(backwards)[junction.switch_for(backwards)].0];
+                    let distance = track.length.min(SWITCH_INDICATOR_LENGTH);
+                    let distance = if backwards {
+                        track.length - distance
+                    } else {
+                        distance
+                    };
+
+                    (
+                        junction.position,
+                        track.shape.get_transform_at_distance(distance).0,
+                    )
+                })
         })
     }
 
+    /// Every train that hasn't been removed, in depots or not
+    pub fn train_ids(&self) -> impl Iterator<Item = TrainId> + '_ {
+        self.trains
+            .iter()
+            .filter(|train| train.location != Location::Removed)
+            .map(|train| train.id)
+    }
+
+    /// Shortest sequence of tracks after `from` that ends at `destination`,
+    /// going forwards or backwards the whole way
+    pub fn plan_route(
+        &self,
+        from: TrackID,
+        backwards: bool,
+        destination: Destination,
+    ) -> Result<Vec<TrackID>, NetworkError> {
+        if from.0 >= self.tracks.len() {
+            return Err(NetworkError::UnknownTrack(from));
+        }
+
+        match destination {
+            Destination::Junction(junction) => {
+                self.junction(junction)?;
+            }
+            Destination::Track(track) if track.0 >= self.tracks.len() => {
+                return Err(NetworkError::UnknownTrack(track));
+            }
+            Destination::Station(station) if station.0 >= self.stations.len() => {
+                return Err(NetworkError::UnknownStation(station));
+            }
+            Destination::Track(_) | Destination::Station(_) => {}
+        }
+
+        routing::shortest_path(
+            (from, backwards),
+            |(track, backwards)| {
+                let junction = &self.junctions[self.tracks[track.0].end(backwards).0];
+
+                junction
+                    .leaving(backwards)
+                    .into_iter()
+                    .map(|next| ((*next, backwards), self.tracks[next.0].length))
+                    .collect::<Vec<_>>()
+            },
+            |(track, backwards)| match destination {
+                Destination::Junction(junction) => self.tracks[track.0].end(backwards) == junction,
+                Destination::Track(goal) => track == goal,
+                Destination::Station(station) => {
+                    self.tracks[track.0].end(backwards) == self.station_exit(station, backwards)
+                }
+            },
+        )
+        .map(|route| route.into_iter().map(|(track, _)| track).collect())
+        .ok_or(NetworkError::NoRoute { from, destination })
+    }
+
+    pub fn set_destination(
+        &mut self,
+        train_id: TrainId,
+        destination: Destination,
+    ) -> Result<(), NetworkError> {
+        let train = self.train_on_network(train_id)?;
+        let route = self.plan_route(train.track, train.backwards, destination)?;
+
+        let train = &mut self.trains[train_id.0];
+        train.destination = Some(destination);
+        train.bound_for = None;
+        train.route = route.into();
+
+        Ok(())
+    }
+
+    /// Turns the train around once it has stopped. The path it reserved ahead
+    /// is given up and its route planned again the other way, dropping its
+    /// destination if that can't be reached anymore.
+    pub fn reverse_train(&mut self, train_id: TrainId) -> Result<(), NetworkError> {
+        self.train_on_network(train_id)?;
+        let train = &mut self.trains[train_id.0];
+
+        // Moving trains brake to a halt first, `update` turns them around then
+        if train.speed > 0.0 {
+            train.reversing = true;
+            return Ok(());
+        }
+
+        self.turn_around(train_id);
+
+        let train = &mut self.trains[train_id.0];
+        train.reversing = false;
+        train.needs_path = true;
+        train.route.clear();
+        for track in train.path.drain(..) {
+            let track = &mut self.tracks[track.0];
+            if track.reserved_by == Some(train_id) {
+                track.reserved_by = None;
+            }
+        }
+
+        if let Some(destination) = train.destination {
+            let bound_for = train.bound_for;
+            match self.set_destination(train_id, destination) {
+                Ok(()) => self.trains[train_id.0].bound_for = bound_for,
+                Err(_) => self.trains[train_id.0].destination = None,
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn nearest_train(&self, position: Vec2, max_distance: f32) -> Option<TrainId> {
+        self.trains
+            .iter()
+            .filter(|train| train.on_network())
+            .map(|train| {
+                let nearest_car = self
+                    .car_transforms(train)
+                    .iter()
+                    .map(|(car, _)| car.distance(position))
+                    .fold(f32::INFINITY, f32::min);
+
+                (train.id, nearest_car)
+            })
+            .filter(|(_, distance)| *distance <= max_distance)
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(id, _)| id)
+    }
+
+    /// Where the middle of the train is
+    pub(crate) fn train_transform(&self, train: &Train) -> (Vec2, f32) {
+        let (track, distance) = self.position_behind(train, train.length() * 0.5);
+
+        self.tracks[track.0]
+            .shape
+            .get_transform_at_distance(distance)
+    }
+
+    pub fn trains<'a>(&'a self) -> impl Iterator<Item = TrainInfo> + 'a {
+        self.trains
+            .iter()
+            .filter(|train| train.on_network())
+            .map(|train| {
+                let (position, angle) = self.train_transform(train);
+
+                TrainInfo {
+                    id: train.id,
+                    position,
+                    angle,
+                    speed: train.speed,
+                    cargo: train.cargo.len(),
+                    capacity: train.capacity(),
+                    travelled: train.travelled,
+                    crashed: train.crashed,
+                }
+            })
+    }
+
+    /// Events since the last call, oldest first
+    pub fn drain_events(&mut self) -> impl Iterator<Item = Event> + '_ {
+        self.events.drain(..)
+    }
+
     pub fn curves<'a>(&'a self) -> impl Iterator<Item = TrackInfo> + 'a {
         self.tracks.iter().map(|track| TrackInfo {
             source: self.junctions[track.source.0].position,
             destination: self.junctions[track.destiation.0].position,
             shape: track.shape,
+            speed_limit: track.speed_limit,
+        })
+    }
+
+    /// The junctions the train will go through next, each with the track it
+    /// will take there, up to the first dead end or `MAX_LOOKAHEAD_TRACKS`.
+    /// Trains follow their reserved path, then their route, then the switches.
+    fn tracks_ahead<'a>(
+        &'a self,
+        train: &'a Train,
+    ) -> impl Iterator<Item = (JunctionId, TrackID)> + 'a {
+        let mut path = train.path.iter().copied().peekable();
+        let mut route = train.route.iter().copied().peekable();
+        let mut track = train.track;
+
+        std::iter::from_fn(move || {
+            let junction = &self.junctions[self.tracks[track.0].end(train.backwards).0];
+            let leaving = junction.leaving(train.backwards);
+            let is_leaving = |next: &TrackID| leaving.into_iter().any(|track| track == next);
+            if leaving.is_empty() {
+                return None;
+            }
+
+            track = match path.next_if(is_leaving) {
+                Some(next) => {
+                    route.next_if_eq(&next);
+                    next
+                }
+                None => route
+                    .next_if(is_leaving)
+                    .unwrap_or(leaving[junction.switch_for(train.backwards)]),
+            };
+
+            Some((junction.id, track))
         })
+        .take(MAX_LOOKAHEAD_TRACKS)
+    }
+
+    /// The track the train will take at the end of its current one, if any
+    fn next_track(&self, train: &Train) -> Option<TrackID> {
+        self.tracks_ahead(train).next().map(|(_, track)| track)
+    }
+
+    /// Signals, the dead end and the station the train is going to less than
+    /// `within` ahead of the front of the train, `remaining` away from the end
+    /// of its track, with how far away each is
+    fn stops_ahead(&self, train: &Train, remaining: f32, within: f32) -> Vec<(Stop, f32)> {
+        let mut stops = vec![];
+        let mut ahead = self.tracks_ahead(train);
+        let mut end = self.tracks[train.track.0].end(train.backwards);
+        let mut gap = remaining;
+        let station = match train.destination {
+            Some(Destination::Station(station)) => Some(station),
+            _ => None,
+        };
+        let depot = train.bound_for.map(|(depot, _)| depot);
+
+        while gap < within {
+            if let Some(station) =
+                station.filter(|station| self.station_exit(*station, train.backwards) == end)
+            {
+                stops.push((Stop::Station(station), gap));
+                break;
+            }
+            if let Some(depot) = depot.filter(|depot| self.depots[depot.0].junction == end) {
+                stops.push((Stop::Depot(depot), gap));
+                break;
+            }
+            let Some((junction, next)) = ahead.next() else {
+                stops.push((Stop::Buffer(end), gap));
+                break;
+            };
+            if self.junctions[junction.0].signal.is_some() {
+                stops.push((Stop::Signal { junction, next }, gap));
+            }
+
+            end = self.tracks[next.0].end(train.backwards);
+            gap += self.tracks[next.0].length;
+        }
+
+        stops
+    }
+
+    /// The fastest the train can go and still slow down in time for every
+    /// speed limit less than `within` ahead of its front, `remaining` away from
+    /// the end of its track, while keeping to the ones of the tracks it's on
+    fn speed_limit_ahead(&self, train: &Train, remaining: f32, within: f32) -> f32 {
+        let mut limit = std::iter::once(&train.track)
+            .chain(&train.trailing)
+            .filter_map(|track| self.tracks[track.0].speed_limit)
+            .fold(f32::INFINITY, f32::min);
+        let mut gap = remaining;
+
+        for (_, next) in self.tracks_ahead(train) {
+            if gap >= within {
+                break;
+            }
+            if let Some(speed_limit) = self.tracks[next.0].speed_limit {
+                limit = limit.min(train.physics.approach_speed(gap, speed_limit));
+            }
+
+            gap += self.tracks[next.0].length;
+        }
+
+        limit
+    }
+
+    pub fn set_auto_reverse(&mut self, auto_reverse: bool) {
+        self.auto_reverse = auto_reverse;
+    }
+
+    /// Junctions trains can get to but not leave in the same direction, where
+    /// they'll stop at a buffer stop
+    pub fn dead_ends(&self) -> impl Iterator<Item = JunctionId> + '_ {
+        self.junctions
+            .iter()
+            .filter(|junction| junction.exits.is_empty() != junction.enterances.is_empty())
+            .map(|junction| junction.id)
+    }
+
+    /// How many times trains have gone through each junction
+    pub fn junction_visits(&self) -> impl Iterator<Item = (JunctionId, usize)> + '_ {
+        self.junctions
+            .iter()
+            .map(|junction| (junction.id, junction.visits))
+    }
+
+    fn enter_track(&mut self, train_index: usize, next_track_id: TrackID) {
+        let train = &mut self.trains[train_index];
+        let previous_track = &self.tracks[train.track.0];
+        train.trailing.push_front(previous_track.id);
+
+        let junction = &mut self.junctions[previous_track.end(train.backwards).0];
+
+        // Trains set facing switches on their route as they pass
+        if train.route.front() == Some(&next_track_id) {
+            train.route.pop_front();
+        }
+        if train.path.front() == Some(&next_track_id) {
+            train.path.pop_front();
+        }
+        junction.set_switch_to(next_track_id);
+        junction.visits += 1;
+
+        let arrived = match train.destination {
+            Some(Destination::Junction(destination)) => destination == junction.id,
+            Some(Destination::Track(destination)) => destination == next_track_id,
+            // Trains only get to a station once they've stopped there
+            Some(Destination::Station(_)) | None => false,
+        };
+        if arrived {
+            train.destination = None;
+            train.route.clear();
+        }
+
+        let overshoot = if train.backwards {
+            -train.distance
+        } else {
+            train.distance - previous_track.length
+        };
+
+        let next_track = &mut self.tracks[next_track_id.0];
+        train.track = next_track_id;
+        train.distance = if train.backwards {
+            next_track.length - overshoot
+        } else {
+            overshoot
+        };
+        next_track.trains.push_back(train.id);
     }
 
+    /// Moves everything on by `delta_time` seconds. Anything but a finite,
+    /// positive time leaves the network as it is.
     pub fn update(&mut self, delta_time: f32) {
-        for train in &mut self.trains {
-            let track = &self.tracks[train.track.0];
+        if !(delta_time.is_finite() && delta_time > 0.0) {
+            return;
+        }
+
+        self.time += delta_time;
+        self.produce_crates(delta_time);
+        self.pay_running_costs(delta_time);
+        self.update_depots(delta_time);
+
+        for index in 0..self.trains.len() {
+            let train = &self.trains[index];
+            if !train.on_network()
+                || train.crashed
+                || (train.needs_path && !self.try_claim_path(train.id))
+            {
+                continue;
+            }
+
+            let train = &mut self.trains[index];
+            if let Some((station, dwell)) = train.dwelling {
+                if dwell > delta_time {
+                    train.dwelling = Some((station, dwell - delta_time));
+                    continue;
+                }
+            }
+
+            let train_id = train.id;
+            // Trains on their way to a depot leave their orders for later
+            if !train.orders.is_empty() && train.bound_for.is_none() {
+                if !self.follow_orders(train_id) {
+                    continue;
+                }
+            } else if let Some((station, _)) = train.dwelling.take() {
+                // Done waiting, trains go on wherever the switches take them
+                if train.destination == Some(Destination::Station(station)) {
+                    train.destination = None;
+                    train.route.clear();
+                }
+                self.events.push(Event::DepartedStation {
+                    train: train_id,
+                    station,
+                    lateness: None,
+                });
+            }
+
+            let train = &self.trains[index];
+            let physics = train.physics;
+            let mut remaining = if train.backwards {
+                train.distance
+            } else {
+                self.tracks[train.track.0].length - train.distance
+            };
 
-            train.distance += delta_time * 8.0;
+            // Far enough to brake for anything the train could get to
+            let speed = (train.speed + physics.acceleration(train.mass()) * delta_time)
+                .min(physics.top_speed);
+            let reach = STOP_DISTANCE + physics.braking_distance(speed) + speed * delta_time;
+            let speed = speed.min(self.speed_limit_ahead(train, remaining, reach));
+
+            // Trains need a signal to be clear before their front reaches it
+            let mut stop = None;
+            for (ahead, gap) in self.stops_ahead(train, remaining, reach) {
+                let train = &self.trains[index];
+                let held = match ahead {
+                    Stop::Signal { junction, next } => {
+                        !train.path.contains(&next)
+                            && !self.try_pass_signal(train.id, junction, next)
+                    }
+                    Stop::Buffer(_) | Stop::Station(_) | Stop::Depot(_) => true,
+                };
+
+                if held {
+                    stop = Some((ahead, (gap - STOP_DISTANCE).max(0.0)));
+                    break;
+                }
+            }
+
+            let train = &mut self.trains[index];
+            let mut speed = if train.reversing {
+                (train.speed - physics.braking * delta_time).max(0.0)
+            } else {
+                speed
+            };
+            let mut step = speed * delta_time;
+            if let Some((_, to_stop)) = stop {
+                speed = speed.min(physics.approach_speed(to_stop, 0.0));
+                step = (speed * delta_time).min(to_stop);
+                if step >= to_stop {
+                    speed = 0.0;
+                }
+            }
+
+            let stopped = train.speed > 0.0 && speed == 0.0;
+            train.speed = speed;
+            train.wear += step;
+            train.travelled += step;
+            if train.backwards {
+                train.distance -= step;
+            } else {
+                train.distance += step;
+            }
+
+            if let Some((Stop::Station(station), to_stop)) = stop {
+                if step >= to_stop {
+                    self.events.push(Event::ArrivedAtStation {
+                        train: train_id,
+                        station,
+                    });
+
+                    if train.orders.is_empty() {
+                        train.dwelling = Some((station, self.stations[station.0].dwell_time));
+                        self.exchange_cargo(train_id, station);
+                    } else {
+                        self.stop_for_orders(train_id, station);
+                    }
+                }
+            }
+            if let Some((Stop::Depot(depot), to_stop)) = stop {
+                if step >= to_stop {
+                    self.enter_depot(train_id, depot);
+                    continue;
+                }
+            }
 
-            if train.distance > track.length.abs() {
-                train.distance %= track.length.abs();
+            let mut turn_around = self.trains[index].reversing && speed == 0.0;
+            if let Some((Stop::Buffer(junction), _)) = stop {
+                if speed == 0.0 {
+                    if stopped {
+                        self.events.push(Event::ReachedDeadEnd {
+                            train: train_id,
+                            junction,
+                        });
+                    }
+                    turn_around |= self.auto_reverse;
+                }
+            }
+            if turn_around {
+                self.reverse_train(train_id).unwrap();
+                continue;
+            }
 
-                let junction = &self.junctions[track.destiation.0];
-                let next_track_id =
-                    junction.exits[rand::thread_rng().gen_range(0..junction.exits.len())];
-                let next_track = &mut self.tracks[next_track_id.0];
-                next_track.trains.push_back(train.id);
+            // Long steps can take the train over several tracks
+            while remaining < step {
+                let Some(next_track) = self.next_track(&self.trains[index]) else {
+                    break;
+                };
+                self.enter_track(index, next_track);
 
-                train.track = next_track.id;
+                step -= remaining;
+                remaining = self.tracks[next_track.0].length;
             }
+            self.trim_trailing(train_id);
         }
+
+        self.detect_collisions();
     }
 }
 
-pub fn generate_network() -> Network {
-    let mut network = Network {
-        tracks: vec![],
-        trains: vec![],
-        junctions: vec![],
-        stations: vec![],
-    };
+pub fn generate_network(seed: u64) -> Network {
+    let mut network = Network::new(seed);
+    // The layout comes for free, spending only starts once it's built
+    network.balance = f32::INFINITY;
 
     let width = 84.0;
     let height = 56.0;
@@ -416,6 +1619,7 @@ pub fn generate_network() -> Network {
             }
         });
 
+        #[cfg(debug_assertions)]
         network.assert_correctness("before new tracks");
         // network.connect_track(junctions[3], center_junction);
         // network.assert_correctness("after first");
@@ -425,13 +1629,271 @@ pub fn generate_network() -> Network {
 
         // network.assert_correctness("After new tracks");
 
-        network.add_train(tracks[0]);
-        network.add_train(tracks[1]);
+        for junction in junctions {
+            network.place_signal(junction).unwrap();
+        }
+
+        // A station on a siding beside the top of the loop, outside the outer
+        // loop and inside the inner one
+        let (from, to) = if offset > 0 {
+            (junctions[3], junctions[4])
+        } else {
+            (junctions[4], junctions[3])
+        };
+        let from_position = network.junctions[from.0].position;
+        let to_position = network.junctions[to.0].position;
+        let direction = (to_position - from_position).normalize();
+        let station = network.add_station(
+            from_position + direction * 8.0 + Vec2::Y * 4.0 * offset as f32,
+            from_position.distance(to_position) - 16.0,
+            direction.to_angle(),
+        );
+        network.connect_track(from, network.get_start_junction(station).unwrap());
+        network.connect_track(network.get_end_junction(station).unwrap(), to);
+
+        network.add_consist(tracks[0], &[1.5, 1.5]);
+        network.add_consist(tracks[1], &[1.5, 1.5]);
+    }
+
+    let inner_bottom = network
+        .nearest_junction(Vec2::new(6.0, -height * 0.5 * 0.8))
+        .unwrap();
+    let inner_right = network
+        .nearest_junction(Vec2::new(width * 0.5 * 0.8, 3.2))
+        .unwrap();
+    network.connect_track(JunctionId(1), inner_bottom);
+    network.connect_track(inner_right, JunctionId(9));
+    for junction in [JunctionId(1), inner_bottom, inner_right, JunctionId(9)] {
+        network.place_path_signal(junction).unwrap();
+    }
+    network.add_depot(JunctionId(0)).unwrap();
+    network.balance = STARTING_BALANCE;
+
+    network
+}
+
+/// Whether the shapes are valid and the path they make starts at `source` and
+/// ends at `destination`
+pub(crate) fn path_fits(shapes: &[TrackShape], source: Vec2, destination: Vec2) -> bool {
+    let (Some(first), Some(last)) = (shapes.first(), shapes.last()) else {
+        return false;
+    };
+
+    shapes.iter().all(TrackShape::is_valid)
+        && first.get_transform_at_distance(0.0).0.distance(source) <= 0.01
+        && last
+            .get_transform_at_distance(last.get_length())
+            .0
+            .distance(destination)
+            <= 0.01
+}
+
+pub(crate) fn check_turning_radius(radius: f32) -> Result<(), NetworkError> {
+    if radius.is_nan() || radius < MIN_RADIUS {
+        return Err(NetworkError::RadiusTooSmall(radius));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::TAU;
+
+    use super::*;
+
+    #[test]
+    fn routes_follow_connected_tracks_to_the_station() {
+        let network = generate_network(0);
+        let train = &network.trains[0];
+
+        for station in network.station_ids() {
+            let route = network
+                .plan_route(train.track, train.backwards, Destination::Station(station))
+                .unwrap();
+
+            let mut track = train.track;
+            for next in &route {
+                let junction = network.tracks[track.0].end(train.backwards);
+                assert_eq!(network.tracks[next.0].end(!train.backwards), junction);
+                track = *next;
+            }
+            assert_eq!(
+                network.tracks[track.0].end(train.backwards),
+                network.station_exit(station, train.backwards)
+            );
+        }
+    }
+
+    /// The saved state of the generated network after its trains have gone
+    /// round every station for a few minutes
+    fn run_generated(seed: u64) -> String {
+        let mut network = generate_network(seed);
+        let orders: Vec<_> = network
+            .station_ids()
+            .flat_map(|station| [Order::GoTo(station), Order::Unload, Order::Load])
+            .collect();
+        for train in network.train_ids().collect::<Vec<_>>() {
+            network.set_orders(train, orders.clone()).unwrap();
+        }
+
+        for _ in 0..60 * 300 {
+            network.update(1.0 / 60.0);
+        }
+
+        network.save()
+    }
+
+    #[test]
+    fn runs_with_the_same_seed_are_identical() {
+        assert_eq!(run_generated(1), run_generated(1));
+    }
+
+    #[test]
+    fn runs_with_different_seeds_differ() {
+        assert_ne!(run_generated(1), run_generated(2));
+    }
+
+    #[test]
+    fn generated_networks_start_with_the_whole_balance() {
+        assert_eq!(generate_network(0).balance, STARTING_BALANCE);
+    }
+
+    #[test]
+    fn updates_only_go_forwards_in_time() {
+        let mut network = generate_network(0);
+        network.update(1.0);
+        let before = network.save();
+
+        for delta_time in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+            network.update(delta_time);
+            assert_eq!(network.save(), before);
+        }
+    }
+
+    #[test]
+    fn long_updates_carry_trains_over_several_segments() {
+        let mut network = Network::new(0);
+        network.balance = f32::INFINITY;
+        let junctions: Vec<_> = (0..10)
+            .map(|i| network.add_junction(Vec2::new(i as f32 * 10.0, 0.0)))
+            .collect();
+        let tracks: Vec<_> = junctions
+            .windows(2)
+            .map(|pair| network.try_connect_track(pair[0], pair[1]).unwrap())
+            .collect();
+        let train = network.try_add_train(tracks[0]).unwrap();
+        network.trains[train.0].speed = network.trains[train.0].physics.top_speed;
+
+        let start = network.trains[train.0].track;
+        network.update(2.0);
+
+        let train = &network.trains[train.0];
+        assert!(train.travelled > 3.0 * IDEAL_SEGMENT_LENGTH);
+        assert_ne!(train.track, start);
+        assert!(network.invariant_violations().is_empty());
+    }
+
+    #[test]
+    fn turning_radius_below_minimum_is_rejected() {
+        let mut network = Network::new(0);
+
+        assert_eq!(
+            network.set_turning_radius(MIN_RADIUS * 0.9),
+            Err(NetworkError::RadiusTooSmall(MIN_RADIUS * 0.9))
+        );
+        assert_eq!(network.turning_radius, MAX_RADIUS);
+        assert_eq!(network.set_turning_radius(MIN_RADIUS), Ok(()));
+    }
+
+    #[test]
+    fn stations_and_junctions_can_be_looked_up() {
+        let mut network = Network::new(0);
+        assert_eq!(network.nearest_junction(Vec2::ZERO), None);
+
+        let station = network.try_add_station(Vec2::ZERO, 3.2, 0.0).unwrap();
+        let start = network.get_start_junction(station).unwrap();
+        let end = network.get_end_junction(station).unwrap();
+        assert_eq!(network.nearest_junction(Vec2::new(-1.0, 0.0)), Some(start));
+        assert_eq!(network.nearest_junction(Vec2::new(4.0, 0.0)), Some(end));
+
+        let missing = StationID(1);
+        assert_eq!(
+            network.get_start_junction(missing),
+            Err(NetworkError::UnknownStation(missing))
+        );
+        assert_eq!(
+            network.get_end_junction(missing),
+            Err(NetworkError::UnknownStation(missing))
+        );
+    }
+
+    #[test]
+    fn stations_that_cant_be_added_leave_nothing_behind() {
+        let mut network = Network::new(0);
+
+        for (position, length) in [
+            (Vec2::ZERO, 0.0),
+            (Vec2::ZERO, f32::NAN),
+            (Vec2::new(f32::INFINITY, 0.0), 3.2),
+        ] {
+            assert!(matches!(
+                network.try_add_station(position, length, 0.0),
+                Err(NetworkError::InvalidStation { .. })
+            ));
+        }
+
+        network.balance = 0.0;
+        assert!(matches!(
+            network.try_add_station(Vec2::ZERO, 3.2, 0.0),
+            Err(NetworkError::InsufficientFunds { .. })
+        ));
+
+        assert!(network.junctions.is_empty());
+        assert!(network.tracks.is_empty());
+        assert!(network.stations.is_empty());
     }
 
-    let length = network.junctions.len();
-    network.connect_track(JunctionId(1), JunctionId(length - 16));
-    network.connect_track(JunctionId(length - 24), JunctionId(9));
+    #[test]
+    fn dwell_times_must_be_finite_and_not_negative() {
+        let mut network = Network::new(0);
+        let station = network.try_add_station(Vec2::ZERO, 3.2, 0.0).unwrap();
 
-    return network;
+        for dwell_time in [-1.0, f32::NAN, f32::INFINITY] {
+            assert!(matches!(
+                network.set_dwell_time(station, dwell_time),
+                Err(NetworkError::InvalidDwellTime(_))
+            ));
+        }
+        assert_eq!(network.set_dwell_time(station, 0.0), Ok(()));
+    }
+
+    #[test]
+    fn connections_at_min_radius_keep_it() {
+        let mut network = Network::new(0);
+        network.balance = f32::INFINITY;
+        network.set_turning_radius(MIN_RADIUS).unwrap();
+
+        for heading in 0..8 {
+            for (x, y) in [(6.0, 0.0), (6.0, 6.0), (0.0, 9.0), (-6.0, 3.0), (2.0, -8.0)] {
+                let from = network.try_add_station(Vec2::ZERO, 3.2, 0.0).unwrap();
+                let to = network
+                    .try_add_station(Vec2::new(x, y), 3.2, TAU * heading as f32 / 8.0)
+                    .unwrap();
+
+                network
+                    .try_connect_track(
+                        network.get_end_junction(from).unwrap(),
+                        network.get_start_junction(to).unwrap(),
+                    )
+                    .unwrap();
+            }
+        }
+
+        for track in &network.tracks {
+            if let Some(radius) = track.shape.min_radius() {
+                assert!(radius >= MIN_RADIUS, "{:?} curves at {radius}", track.id);
+            }
+        }
+    }
 }
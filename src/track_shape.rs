@@ -1,8 +1,9 @@
 use std::f32::consts::{FRAC_PI_2, PI, TAU};
 
 use glam::Vec2;
+use serde::{Deserialize, Serialize};
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum TrackShape {
     Line {
         source: Vec2,
@@ -15,8 +16,38 @@ pub enum TrackShape {
         radius: f32,
         center: Vec2,
     },
+    Clothoid {
+        source: Vec2,
+        start_angle: f32,
+        start_curvature: f32,
+        sharpness: f32,
+        length: f32,
+    },
+    Bezier {
+        points: [Vec2; 4],
+        /// Arc length of the whole curve, worked out once as it's needed all
+        /// the time
+        length: f32,
+    },
 }
 
+const CLOTHOID_INTEGRATION_STEP: f32 = 0.25;
+/// How many points along a bézier its curvature is checked at
+const BEZIER_SAMPLES: usize = 33;
+/// Bézier arc lengths are integrated over this many pieces of the curve
+const BEZIER_LENGTH_PIECES: usize = 8;
+/// Gauss-Legendre nodes on [-1, 1] and their weights
+const GAUSS_LEGENDRE: [(f32, f32); 5] = [
+    (-0.906_179_85, 0.236_926_88),
+    (-0.538_469_3, 0.478_628_67),
+    (0.0, 0.568_888_9),
+    (0.538_469_3, 0.478_628_67),
+    (0.906_179_85, 0.236_926_88),
+];
+/// How close moving along a bézier gets to the distance asked for, in metres
+const BEZIER_DISTANCE_TOLERANCE: f32 = 1e-4;
+const BEZIER_NEWTON_STEPS: usize = 8;
+
 impl TrackShape {
     pub fn from_source_direction_dest(
         source: Vec2,
@@ -70,7 +101,160 @@ impl TrackShape {
         shape
     }
 
+    /// Like `from_source_direction_dest`, but eases into and out of the arc with
+    /// clothoids so the curvature is zero at both ends. The end direction is the
+    /// same as that of the plain arc.
+    pub fn eased_from_source_direction_dest(
+        source: Vec2,
+        source_direction: Vec2,
+        destination: Vec2,
+    ) -> Vec<TrackShape> {
+        match TrackShape::from_source_direction_dest(source, source_direction, destination) {
+            TrackShape::Arc { angle_diff, .. } => {
+                Self::eased_turn(source, source_direction, destination, angle_diff)
+            }
+            line => vec![line],
+        }
+    }
+
+    /// Eased curve turning by `angle_diff` between two points, which must lie on
+    /// a common circle tangent to `source_direction`.
+    pub fn eased_turn(
+        source: Vec2,
+        source_direction: Vec2,
+        destination: Vec2,
+        angle_diff: f32,
+    ) -> Vec<TrackShape> {
+        let sign = angle_diff.signum();
+        let total_angle = angle_diff.abs();
+
+        // Past half a turn the chord of the eased curve no longer points the
+        // same way as that of the arc, so ease both halves separately instead
+        if total_angle > PI {
+            let circle_radius = destination.distance(source) / (2.0 * (total_angle / 2.0).sin());
+            let center = source + source_direction.perp() * sign * circle_radius;
+            let half_turn = Vec2::from_angle(angle_diff / 2.0);
+            let middle = center + half_turn.rotate(source - center);
+
+            let mut shapes = Self::eased_turn(source, source_direction, middle, angle_diff / 2.0);
+            shapes.extend(Self::eased_turn(
+                middle,
+                half_turn.rotate(source_direction),
+                destination,
+                angle_diff / 2.0,
+            ));
+            return shapes;
+        }
+
+        // Each spiral takes a quarter of the turn, the arc the remaining half.
+        // The shape is then fixed up to scale, so build it with a unit radius
+        // and scale it to fit the chord.
+        let unit_spiral_length = total_angle / 2.0;
+
+        let unit_shapes =
+            Self::easement_pieces(Vec2::ZERO, 0.0, 1.0, 1.0, unit_spiral_length, total_angle);
+        let unit_chord = unit_shapes[2]
+            .get_transform_at_distance(unit_shapes[2].get_length())
+            .0
+            .length();
+
+        let radius = destination.distance(source) / unit_chord;
+
+        Vec::from(Self::easement_pieces(
+            source,
+            source_direction.to_angle(),
+            sign,
+            radius,
+            unit_spiral_length * radius,
+            total_angle,
+        ))
+    }
+
+    /// Cubic bézier from one directed point to another, which allows S-bends
+    /// that a single arc can't make.
+    pub fn from_source_direction_dest_direction(
+        source: Vec2,
+        source_direction: Vec2,
+        destination: Vec2,
+        destination_direction: Vec2,
+    ) -> TrackShape {
+        let handle_length = source.distance(destination) / 3.0;
+
+        TrackShape::bezier([
+            source,
+            source + source_direction * handle_length,
+            destination - destination_direction * handle_length,
+            destination,
+        ])
+    }
+
+    pub fn bezier(points: [Vec2; 4]) -> TrackShape {
+        TrackShape::Bezier {
+            points,
+            length: bezier_length_to(points, 1.0),
+        }
+    }
+
+    /// The `t` the curve is `distance` along at, found with Newton's method
+    /// from where it would be if the curve were travelled at constant speed
+    fn bezier_t_at_distance(points: [Vec2; 4], length: f32, distance: f32) -> f32 {
+        if length <= 0.0 {
+            return 0.0;
+        }
+
+        let mut t = (distance / length).clamp(0.0, 1.0);
+        for _ in 0..BEZIER_NEWTON_STEPS {
+            let error = bezier_length_to(points, t) - distance;
+            let speed = bezier_derivative(points, t).length();
+            if error.abs() < BEZIER_DISTANCE_TOLERANCE || speed <= f32::EPSILON {
+                break;
+            }
+
+            t = (t - error / speed).clamp(0.0, 1.0);
+        }
+
+        t
+    }
+
+    fn easement_pieces(
+        source: Vec2,
+        start_angle: f32,
+        sign: f32,
+        radius: f32,
+        spiral_length: f32,
+        total_angle: f32,
+    ) -> [TrackShape; 3] {
+        let entry = TrackShape::Clothoid {
+            source,
+            start_angle,
+            start_curvature: 0.0,
+            sharpness: sign / (radius * spiral_length),
+            length: spiral_length,
+        };
+        let (entry_end, entry_angle) = entry.get_transform_at_distance(spiral_length);
+
+        let center = entry_end + Vec2::from_angle(entry_angle).perp() * sign * radius;
+        let arc = TrackShape::Arc {
+            start_angle: (entry_end - center).to_angle(),
+            angle_diff: sign * (total_angle - spiral_length / radius),
+            radius,
+            center,
+        };
+        let (arc_end, arc_angle) = arc.get_transform_at_distance(arc.get_length());
+
+        let exit = TrackShape::Clothoid {
+            source: arc_end,
+            start_angle: arc_angle,
+            start_curvature: sign / radius,
+            sharpness: -sign / (radius * spiral_length),
+            length: spiral_length,
+        };
+
+        [entry, arc, exit]
+    }
+
     #[cfg(debug_assertions)]
+    #[allow(clippy::too_many_arguments)]
     fn assert_sanity(
         &self,
         signed_radius: f32,
@@ -159,6 +343,26 @@ impl TrackShape {
                 radius,
                 center,
             },
+            TrackShape::Clothoid {
+                start_curvature,
+                sharpness,
+                length,
+                ..
+            } => {
+                let (end, end_angle) = self.get_transform_at_distance(length);
+
+                TrackShape::Clothoid {
+                    source: end,
+                    start_angle: end_angle + PI,
+                    start_curvature: -(start_curvature + sharpness * length),
+                    sharpness,
+                    length,
+                }
+            }
+            TrackShape::Bezier { points, length } => TrackShape::Bezier {
+                points: [points[3], points[2], points[1], points[0]],
+                length,
+            },
         }
     }
 
@@ -180,15 +384,121 @@ impl TrackShape {
                     angle + FRAC_PI_2 * angle_diff.signum(),
                 )
             }
+            TrackShape::Clothoid {
+                source,
+                start_angle,
+                start_curvature,
+                sharpness,
+                ..
+            } => {
+                let angle_at = |s: f32| start_angle + start_curvature * s + sharpness * s * s / 2.0;
+
+                // Simpson's rule, the Fresnel integrals have no closed form
+                let steps =
+                    ((distance.abs() / CLOTHOID_INTEGRATION_STEP).ceil() as usize).max(1) * 2;
+                let step = distance / steps as f32;
+
+                let offset = (0..=steps).fold(Vec2::ZERO, |total, i| {
+                    let weight = if i == 0 || i == steps {
+                        1.0
+                    } else if i % 2 == 1 {
+                        4.0
+                    } else {
+                        2.0
+                    };
+
+                    total + Vec2::from_angle(angle_at(i as f32 * step)) * weight
+                }) * step
+                    / 3.0;
+
+                (*source + offset, angle_at(distance))
+            }
+            TrackShape::Bezier { points, length } => {
+                let t = Self::bezier_t_at_distance(*points, *length, distance);
+
+                (
+                    bezier_point(*points, t),
+                    bezier_derivative(*points, t).to_angle(),
+                )
+            }
         }
     }
 
+    /// Whether every parameter is finite, the length is positive and a
+    /// bézier's length is its arc length. Shapes read from a save might not be.
+    pub(crate) fn is_valid(&self) -> bool {
+        let finite = match *self {
+            TrackShape::Line {
+                source,
+                direction,
+                length,
+            } => source.is_finite() && direction.is_normalized() && length.is_finite(),
+            TrackShape::Arc {
+                start_angle,
+                angle_diff,
+                radius,
+                center,
+            } => {
+                [start_angle, angle_diff, radius]
+                    .iter()
+                    .all(|value| value.is_finite())
+                    && center.is_finite()
+            }
+            TrackShape::Clothoid {
+                source,
+                start_angle,
+                start_curvature,
+                sharpness,
+                length,
+            } => {
+                [start_angle, start_curvature, sharpness, length]
+                    .iter()
+                    .all(|value| value.is_finite())
+                    && source.is_finite()
+            }
+            TrackShape::Bezier { points, length } => {
+                points.iter().all(|point| point.is_finite())
+                    && (length - bezier_length_to(points, 1.0)).abs()
+                        <= BEZIER_DISTANCE_TOLERANCE * length.max(1.0)
+            }
+        };
+
+        finite && self.get_length() > 0.0
+    }
+
     pub fn get_length(&self) -> f32 {
         match self {
             TrackShape::Line { length, .. } => *length,
             TrackShape::Arc {
                 angle_diff, radius, ..
             } => (*angle_diff * *radius).abs(),
+            TrackShape::Clothoid { length, .. } => *length,
+            TrackShape::Bezier { length, .. } => *length,
+        }
+    }
+
+    /// Tightest radius along the shape, `None` if it is straight
+    pub fn min_radius(&self) -> Option<f32> {
+        let max_curvature = match self {
+            TrackShape::Line { .. } => 0.0,
+            TrackShape::Arc { radius, .. } => 1.0 / radius,
+            TrackShape::Clothoid {
+                start_curvature,
+                sharpness,
+                length,
+                ..
+            } => start_curvature
+                .abs()
+                .max((start_curvature + sharpness * length).abs()),
+            TrackShape::Bezier { points, .. } => (0..BEZIER_SAMPLES)
+                .map(|i| bezier_curvature(*points, i as f32 / (BEZIER_SAMPLES - 1) as f32).abs())
+                .fold(0.0, f32::max),
+        };
+
+        if max_curvature > 0.0 {
+            Some(1.0 / max_curvature)
+        } else {
+            None
         }
     }
 
@@ -212,6 +522,247 @@ impl TrackShape {
                 radius: *radius,
                 center: *center,
             },
+            TrackShape::Clothoid {
+                start_curvature,
+                sharpness,
+                ..
+            } => {
+                let (source, start_angle) = self.get_transform_at_distance(from);
+
+                TrackShape::Clothoid {
+                    source,
+                    start_angle,
+                    start_curvature: start_curvature + sharpness * from,
+                    sharpness: *sharpness,
+                    length: to - from,
+                }
+            }
+            TrackShape::Bezier { points, length } => {
+                let from_t = Self::bezier_t_at_distance(*points, *length, from);
+                let to_t = Self::bezier_t_at_distance(*points, *length, to);
+
+                let tail = split_bezier(*points, from_t).1;
+                let middle =
+                    split_bezier(tail, (to_t - from_t) / (1.0 - from_t).max(f32::EPSILON)).0;
+
+                TrackShape::bezier(middle)
+            }
+        }
+    }
+}
+
+fn bezier_point(points: [Vec2; 4], t: f32) -> Vec2 {
+    let u = 1.0 - t;
+
+    points[0] * (u * u * u)
+        + points[1] * (3.0 * u * u * t)
+        + points[2] * (3.0 * u * t * t)
+        + points[3] * (t * t * t)
+}
+
+fn bezier_derivative(points: [Vec2; 4], t: f32) -> Vec2 {
+    let u = 1.0 - t;
+
+    (points[1] - points[0]) * (3.0 * u * u)
+        + (points[2] - points[1]) * (6.0 * u * t)
+        + (points[3] - points[2]) * (3.0 * t * t)
+}
+
+/// Arc length from the start of the curve to `t`
+fn bezier_length_to(points: [Vec2; 4], t: f32) -> f32 {
+    let piece = t / BEZIER_LENGTH_PIECES as f32;
+
+    (0..BEZIER_LENGTH_PIECES)
+        .map(|index| {
+            let middle = (index as f32 + 0.5) * piece;
+
+            GAUSS_LEGENDRE
+                .iter()
+                .map(|(node, weight)| {
+                    weight * bezier_derivative(points, middle + node * piece * 0.5).length()
+                })
+                .sum::<f32>()
+                * piece
+                * 0.5
+        })
+        .sum()
+}
+
+fn bezier_second_derivative(points: [Vec2; 4], t: f32) -> Vec2 {
+    (points[2] - points[1] * 2.0 + points[0]) * (6.0 * (1.0 - t))
+        + (points[3] - points[2] * 2.0 + points[1]) * (6.0 * t)
+}
+
+fn bezier_curvature(points: [Vec2; 4], t: f32) -> f32 {
+    let derivative = bezier_derivative(points, t);
+
+    derivative.perp_dot(bezier_second_derivative(points, t)) / derivative.length().powi(3)
+}
+
+/// De Casteljau subdivision at `t`
+fn split_bezier(points: [Vec2; 4], t: f32) -> ([Vec2; 4], [Vec2; 4]) {
+    let a = points[0].lerp(points[1], t);
+    let b = points[1].lerp(points[2], t);
+    let c = points[2].lerp(points[3], t);
+    let d = a.lerp(b, t);
+    let e = b.lerp(c, t);
+    let f = d.lerp(e, t);
+
+    ([points[0], a, d, f], [f, e, c, points[3]])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const S_BEND: [Vec2; 4] = [
+        Vec2::new(0.0, 0.0),
+        Vec2::new(4.0, 0.0),
+        Vec2::new(2.0, 5.0),
+        Vec2::new(8.0, 6.0),
+    ];
+
+    #[test]
+    fn bezier_length_matches_polyline() {
+        let steps = 10_000;
+        let polyline: f32 = (1..=steps)
+            .map(|step| {
+                bezier_point(S_BEND, step as f32 / steps as f32)
+                    .distance(bezier_point(S_BEND, (step - 1) as f32 / steps as f32))
+            })
+            .sum();
+
+        let length = TrackShape::bezier(S_BEND).get_length();
+        assert!((length - polyline).abs() < 1e-3, "{length} vs {polyline}");
+    }
+
+    #[test]
+    fn bezier_moves_at_constant_speed() {
+        let shape = TrackShape::bezier(S_BEND);
+        let length = shape.get_length();
+        let steps = 50;
+
+        for step in 1..=steps {
+            let before = shape.get_transform_at_distance(length * (step - 1) as f32 / steps as f32);
+            let after = shape.get_transform_at_distance(length * step as f32 / steps as f32);
+            let gap = before.0.distance(after.0);
+
+            assert!(
+                (gap - length / steps as f32).abs() < 1e-3,
+                "step {step}: {gap}"
+            );
+        }
+    }
+
+    #[test]
+    fn reversed_and_partial_beziers_keep_their_length() {
+        let shape = TrackShape::bezier(S_BEND);
+        let length = shape.get_length();
+
+        assert_eq!(shape.reverse().get_length(), length);
+        let end = shape.get_transform_at_distance(length).0;
+        assert!(
+            shape
+                .reverse()
+                .get_transform_at_distance(0.0)
+                .0
+                .distance(end)
+                < 1e-4
+        );
+
+        let middle = shape.subshape(length * 0.25, length * 0.75);
+        assert!((middle.get_length() - length * 0.5).abs() < 1e-3);
+    }
+
+    const CLOTHOID: TrackShape = TrackShape::Clothoid {
+        source: Vec2::new(1.0, 2.0),
+        start_angle: 0.3,
+        start_curvature: 0.05,
+        sharpness: 0.02,
+        length: 7.0,
+    };
+
+    fn assert_same_transform(a: (Vec2, f32), b: (Vec2, f32)) {
+        assert!(a.0.distance(b.0) < 1e-3, "{a:?} vs {b:?}");
+        assert!(
+            Vec2::from_angle(a.1).distance(Vec2::from_angle(b.1)) < 1e-3,
+            "{a:?} vs {b:?}"
+        );
+    }
+
+    /// Checks each piece starts where the one before it ends, going the same way
+    fn assert_joined(shapes: &[TrackShape]) {
+        for pair in shapes.windows(2) {
+            assert_same_transform(
+                pair[0].get_transform_at_distance(pair[0].get_length()),
+                pair[1].get_transform_at_distance(0.0),
+            );
+        }
+    }
+
+    #[test]
+    fn reversed_clothoids_run_back_along_the_same_curve() {
+        let reversed = CLOTHOID.reverse();
+        let length = CLOTHOID.get_length();
+        assert_eq!(reversed.get_length(), length);
+
+        for step in 0..=10 {
+            let distance = length * step as f32 / 10.0;
+            let (position, angle) = CLOTHOID.get_transform_at_distance(length - distance);
+            assert_same_transform(
+                reversed.get_transform_at_distance(distance),
+                (position, angle + PI),
+            );
+        }
+    }
+
+    #[test]
+    fn clothoid_subshapes_follow_the_whole_curve() {
+        let pieces = [0.0, 2.0, 4.5, 7.0]
+            .windows(2)
+            .map(|ends| (ends[0], ends[1]));
+        let subshapes: Vec<_> = pieces
+            .clone()
+            .map(|(from, to)| CLOTHOID.subshape(from, to))
+            .collect();
+        assert_joined(&subshapes);
+
+        for ((from, to), subshape) in pieces.zip(&subshapes) {
+            assert!((subshape.get_length() - (to - from)).abs() < 1e-6);
+            for distance in [0.0, (to - from) * 0.5, to - from] {
+                assert_same_transform(
+                    subshape.get_transform_at_distance(distance),
+                    CLOTHOID.get_transform_at_distance(from + distance),
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn eased_turns_join_up_and_end_at_the_destination() {
+        let source = Vec2::new(3.0, -1.0);
+        let source_direction = Vec2::from_angle(0.4);
+        let radius = 5.0;
+
+        for angle_diff in [0.3, -FRAC_PI_2, PI - 0.1, PI + 0.5, -1.8 * PI] {
+            let center = source + source_direction.perp() * angle_diff.signum() * radius;
+            let destination = center + Vec2::from_angle(angle_diff).rotate(source - center);
+
+            let shapes = TrackShape::eased_turn(source, source_direction, destination, angle_diff);
+
+            // Turns past half a circle are eased in two halves
+            let halves = if angle_diff.abs() > PI { 2 } else { 1 };
+            assert_eq!(shapes.len(), 3 * halves);
+            assert_joined(&shapes);
+            assert_same_transform(
+                shapes[0].get_transform_at_distance(0.0),
+                (source, source_direction.to_angle()),
+            );
+            let last = shapes[shapes.len() - 1];
+            assert_same_transform(
+                last.get_transform_at_distance(last.get_length()),
+                (destination, source_direction.to_angle() + angle_diff),
+            );
         }
     }
 }
@@ -0,0 +1,414 @@
+use std::f32::consts::{PI, TAU};
+
+use crates::{
+    consist::{CarInfo, CarKind},
+    depot::DepotVisit,
+    proper_draw_arc::draw_arc,
+    track::{generate_network, Destination, Event, Network, TRAIN_WIDTH},
+    track_shape::TrackShape,
+};
+use glam::Vec2;
+use macroquad::{
+    camera::{set_camera, set_default_camera, Camera2D},
+    color::{
+        Color, BLACK, BROWN, DARKBLUE, DARKGRAY, DARKGREEN, GREEN, LIGHTGRAY, ORANGE, RED, WHITE,
+    },
+    input::{is_key_pressed, is_mouse_button_pressed, mouse_position, KeyCode, MouseButton},
+    math::Rect,
+    miniquad::window,
+    shapes::{draw_circle, draw_line, draw_rectangle_ex, DrawRectangleParams},
+    text::draw_text,
+    window::{clear_background, next_frame},
+};
+
+use crate::{orders_round_every_station, TICK};
+
+/// Longer frames are cut short, so the simulation slows down after a hitch
+/// rather than trying to catch up all at once
+const MAX_FRAME_TIME: f32 = 0.25;
+/// Where F5 saves the network and F9 loads it from
+const SAVE_PATH: &str = "network.ron";
+
+fn draw_all_arcs(network: &Network, thickness: f32, color: Color) {
+    for curve in network.curves() {
+        match curve.shape {
+            TrackShape::Line { source, .. } => draw_line(
+                source.x,
+                source.y,
+                curve.destination.x,
+                curve.destination.y,
+                thickness,
+                color,
+            ),
+            TrackShape::Arc {
+                start_angle,
+                angle_diff,
+                radius,
+                center,
+            } => {
+                let (start_angle, angle_diff) = if angle_diff < 0. {
+                    (start_angle + angle_diff, -angle_diff)
+                } else {
+                    (start_angle, angle_diff)
+                };
+
+                draw_arc(
+                    center,
+                    radius,
+                    start_angle,
+                    angle_diff,
+                    40,
+                    thickness,
+                    color,
+                );
+            }
+            TrackShape::Clothoid { .. } | TrackShape::Bezier { .. } => {
+                draw_sampled_curve(&curve.shape, thickness, color)
+            }
+        }
+    }
+}
+
+fn draw_sampled_curve(shape: &TrackShape, thickness: f32, color: Color) {
+    let length = shape.get_length();
+    let steps = (length / 0.5).ceil().max(1.0) as usize;
+
+    for i in 0..steps {
+        let from = shape
+            .get_transform_at_distance(length * i as f32 / steps as f32)
+            .0;
+        let to = shape
+            .get_transform_at_distance(length * (i + 1) as f32 / steps as f32)
+            .0;
+
+        draw_line(from.x, from.y, to.x, to.y, thickness, color);
+    }
+}
+
+/// The cars `alpha` of the way from where they were before the last update to
+/// where they are now. Cars that jumped, like when their train turned around,
+/// are drawn where they are.
+fn interpolate_cars(
+    previous: &[CarInfo],
+    current: impl Iterator<Item = CarInfo>,
+    alpha: f32,
+) -> Vec<CarInfo> {
+    let mut train = None;
+    let mut index = 0;
+
+    current
+        .map(|car| {
+            index = if train == Some(car.train) {
+                index + 1
+            } else {
+                0
+            };
+            train = Some(car.train);
+
+            let before = previous
+                .iter()
+                .filter(|before| before.train == car.train)
+                .nth(index);
+
+            match before {
+                Some(before) if before.position.distance(car.position) < 1.0 => {
+                    let turn = (car.angle - before.angle + PI).rem_euclid(TAU) - PI;
+
+                    CarInfo {
+                        position: before.position.lerp(car.position, alpha),
+                        angle: before.angle + turn * alpha,
+                        ..car
+                    }
+                }
+                _ => car,
+            }
+        })
+        .collect()
+}
+
+fn window_conf() -> macroquad::window::Conf {
+    macroquad::window::Conf {
+        window_title: "Crates".to_owned(),
+        sample_count: 4,
+        ..Default::default()
+    }
+}
+
+/// Opens the window and runs a generated network in it until it's closed
+pub fn run() {
+    macroquad::Window::from_config(window_conf(), run_window());
+}
+
+async fn run_window() {
+    // Runs can be repeated by passing the seed they print
+    let seed = std::env::args()
+        .nth(1)
+        .and_then(|seed| seed.parse().ok())
+        .unwrap_or_else(rand::random);
+    eprintln!("seed {seed}");
+
+    let mut network = generate_network(seed);
+    for junction in network.dead_ends() {
+        eprintln!("dead end at {junction:?}");
+    }
+
+    let size = 64.0f32;
+    let mut previous_cars: Vec<_> = network.cars().collect();
+    let mut accumulator = 0.0;
+
+    loop {
+        accumulator += macroquad::time::get_frame_time().min(MAX_FRAME_TIME);
+        while accumulator >= TICK {
+            previous_cars = network.cars().collect();
+            network.update(TICK);
+            accumulator -= TICK;
+        }
+
+        for event in network.drain_events() {
+            match event {
+                Event::Crash { trains, position } => {
+                    eprintln!("{:?} and {:?} crashed at {position}", trains[0], trains[1])
+                }
+                Event::ReachedDeadEnd { train, junction } => {
+                    eprintln!("{train:?} stopped at the dead end at {junction:?}")
+                }
+                Event::ArrivedAtStation { train, station } => {
+                    eprintln!("{train:?} arrived at {station:?}")
+                }
+                Event::DepartedStation {
+                    train,
+                    station,
+                    lateness,
+                } => match lateness {
+                    Some(lateness) if lateness > 0.0 => {
+                        eprintln!("{train:?} departed from {station:?} {lateness:.1}s late")
+                    }
+                    _ => eprintln!("{train:?} departed from {station:?}"),
+                },
+                Event::CrateDelivered {
+                    train,
+                    cargo,
+                    revenue,
+                } => {
+                    eprintln!(
+                        "{train:?} delivered a crate from {:?} to {:?} for {revenue:.0}",
+                        cargo.origin, cargo.destination
+                    )
+                }
+                Event::EnteredDepot { train, depot } => {
+                    eprintln!("{train:?} went into {depot:?}")
+                }
+                Event::LeftDepot { train, depot } => eprintln!("{train:?} left {depot:?}"),
+            }
+        }
+
+        let screen_size = window::screen_size();
+        let aspect = screen_size.0 / screen_size.1;
+
+        clear_background(WHITE);
+
+        let camera = Camera2D::from_display_rect(Rect::new(
+            -size * 0.5 * aspect,
+            -size * 0.5,
+            size * aspect,
+            size,
+        ));
+        set_camera(&camera);
+
+        let mouse = camera.screen_to_world(mouse_position().into());
+        let mouse = Vec2::new(mouse.x, mouse.y);
+
+        if is_mouse_button_pressed(MouseButton::Left) {
+            if let Some(switch) = network.nearest_switch(mouse, 2.0) {
+                // Switches on a reserved path stay where they are
+                let _ = network.throw_switch(switch);
+            }
+        }
+
+        if is_key_pressed(KeyCode::F5) {
+            match std::fs::write(SAVE_PATH, network.save()) {
+                Ok(()) => eprintln!("saved to {SAVE_PATH}"),
+                Err(error) => eprintln!("couldn't save to {SAVE_PATH}: {error}"),
+            }
+        }
+
+        if is_key_pressed(KeyCode::F9) {
+            let loaded = std::fs::read_to_string(SAVE_PATH)
+                .map_err(|error| error.to_string())
+                .and_then(|text| Network::load(&text).map_err(|error| error.to_string()));
+
+            match loaded {
+                Ok(loaded) => {
+                    network = loaded;
+                    previous_cars = network.cars().collect();
+                    eprintln!("loaded {SAVE_PATH}");
+                }
+                Err(error) => eprintln!("couldn't load {SAVE_PATH}: {error}"),
+            }
+        }
+
+        if is_key_pressed(KeyCode::R) {
+            if let Some(train) = network.nearest_train(mouse, 2.0) {
+                network.reverse_train(train).unwrap();
+            }
+        }
+
+        if let Some(depot) = network.depot_ids().next() {
+            if is_key_pressed(KeyCode::B) {
+                match network.buy_train(depot, &[1.5, 1.5]) {
+                    Ok(train) => network.dispatch_train(train).unwrap(),
+                    Err(error) => eprintln!("{error}"),
+                }
+            }
+
+            if is_key_pressed(KeyCode::L) {
+                for train in network.stored_trains(depot).collect::<Vec<_>>() {
+                    network.dispatch_train(train).unwrap();
+                }
+            }
+
+            for (key, visit) in [
+                (KeyCode::D, DepotVisit::Store),
+                (KeyCode::S, DepotVisit::Service),
+            ] {
+                if is_key_pressed(key) {
+                    if let Some(train) = network.nearest_train(mouse, 2.0) {
+                        // Trains with no way there stay where they are
+                        let _ = network.send_to_depot(train, depot, visit);
+                    }
+                }
+            }
+        }
+
+        if is_key_pressed(KeyCode::X) {
+            if let Some(train) = network.nearest_train(mouse, 2.0) {
+                network.remove_train(train).unwrap();
+            }
+        }
+
+        if is_key_pressed(KeyCode::O) {
+            if let Some(train) = network.nearest_train(mouse, 2.0) {
+                let orders = orders_round_every_station(&network);
+                network.set_orders(train, orders).unwrap();
+            }
+        }
+
+        if is_mouse_button_pressed(MouseButton::Right) {
+            let destination = match network.nearest_station(mouse, 2.0) {
+                Some(station) => Some(Destination::Station(station)),
+                None => network.nearest_junction(mouse).map(Destination::Junction),
+            };
+
+            for train in network.train_ids().collect::<Vec<_>>() {
+                // Trains that can't get there keep going where they were going
+                if let Some(destination) = destination {
+                    let _ = network.set_destination(train, destination);
+                }
+            }
+        }
+
+        for station in network.stations() {
+            draw_rectangle_ex(
+                station.position.x,
+                station.position.y,
+                station.length,
+                3.0,
+                DrawRectangleParams {
+                    color: LIGHTGRAY,
+                    rotation: station.angle,
+                    offset: macroquad::math::Vec2::new(0.0, 0.5),
+                },
+            );
+
+            // As many of the waiting crates as fit along the platform
+            let direction = Vec2::from_angle(station.angle);
+            for index in 0..station.waiting.min((station.length / 0.9) as usize) {
+                let position = station.position
+                    + direction * (0.45 + index as f32 * 0.9)
+                    + direction.perp() * 1.1;
+
+                draw_rectangle_ex(
+                    position.x,
+                    position.y,
+                    0.6,
+                    0.6,
+                    DrawRectangleParams {
+                        color: BROWN,
+                        rotation: station.angle,
+                        offset: macroquad::math::Vec2::new(0.5, 0.5),
+                    },
+                );
+            }
+        }
+
+        for depot in network.depots() {
+            draw_rectangle_ex(
+                depot.position.x,
+                depot.position.y,
+                4.0,
+                4.0,
+                DrawRectangleParams {
+                    color: DARKGRAY,
+                    offset: macroquad::math::Vec2::new(0.5, 0.5),
+                    ..Default::default()
+                },
+            );
+        }
+
+        draw_all_arcs(&network, 1.0, DARKBLUE);
+        draw_all_arcs(&network, 0.8, Color::from_hex(0xFFFFFF));
+        draw_all_arcs(&network, 0.1, DARKBLUE);
+
+        for (position, set_to) in network.switch_positions() {
+            draw_line(position.x, position.y, set_to.x, set_to.y, 0.4, ORANGE);
+        }
+
+        for (position, clear) in network.signal_states() {
+            draw_circle(position.x, position.y, 0.5, if clear { GREEN } else { RED });
+        }
+
+        for car in interpolate_cars(&previous_cars, network.cars(), accumulator / TICK) {
+            let (position, angle) = (car.position, car.angle);
+            let color = match car.kind {
+                _ if car.crashed => RED,
+                CarKind::Locomotive => GREEN,
+                CarKind::Wagon => DARKGREEN,
+            };
+
+            draw_rectangle_ex(
+                position.x,
+                position.y,
+                car.length,
+                TRAIN_WIDTH,
+                DrawRectangleParams {
+                    color,
+                    rotation: angle,
+                    offset: macroquad::math::Vec2::new(0.5, 0.5),
+                },
+            );
+
+            draw_rectangle_ex(
+                position.x,
+                position.y,
+                car.length - 0.2,
+                TRAIN_WIDTH - 0.2,
+                DrawRectangleParams {
+                    color: WHITE,
+                    rotation: angle,
+                    offset: macroquad::math::Vec2::new(0.5, 0.5),
+                },
+            );
+        }
+
+        set_default_camera();
+        draw_text(
+            &format!("Balance: {:.0}", network.balance()),
+            10.0,
+            30.0,
+            30.0,
+            if network.balance() < 0.0 { RED } else { BLACK },
+        );
+
+        next_frame().await
+    }
+}